@@ -0,0 +1,308 @@
+use crate::format::ParseError;
+use crate::render::{self, Renderer};
+use crate::strict;
+use handlebars::template::{Parameter, Template, TemplateElement, TemplateMapping};
+use handlebars::{Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason, ScopedJson};
+use mdbook::book::{BookItem, Chapter};
+use mdbook::errors::Error;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+/// A problem found in a chapter, partial or data file.
+pub struct Diagnostic {
+    /// Path relative to the book directory, or for data files as configured in `paths`.
+    pub file: String,
+    /// 1-based line and column, when known.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}:{}: {}", self.file, line, column, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// `mdbook-template check <book-dir>`: loads the book and reports syntax errors, undefined
+/// variables, unknown helpers and missing partials in every chapter, one `file:line:column: message`
+/// per line on stdout. Returns whether the book is free of problems.
+///
+/// Undefined variables are reported whether or not `strict` is set, minus those allowed by
+/// `allow-missing`. They are found by rendering, so unlike `mdbook-template keys` (see
+/// [`crate::keys::report`]) only what a render reaches is checked.
+pub fn run(dir: &Path) -> Result<bool, Error> {
+    let book = render::load_book(dir)?;
+    let renderer = Renderer::new(&book.config, &book.root, "html")?;
+
+    let mut diagnostics = Vec::new();
+    for item in book.iter() {
+        let BookItem::Chapter(ch) = item else {
+            continue;
+        };
+        // Draft chapters have no file.
+        let Some(source_path) = &ch.source_path else {
+            continue;
+        };
+        let file = book.config.book.src.join(source_path).display().to_string();
+        diagnostics.extend(check_chapter(&renderer, ch, &file));
+    }
+
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    if diagnostics.is_empty() {
+        eprintln!("no problems found");
+    } else {
+        eprintln!("{} problem(s) found", diagnostics.len());
+    }
+    Ok(diagnostics.is_empty())
+}
+
+fn check_chapter(renderer: &Renderer, ch: &Chapter, file: &str) -> Vec<Diagnostic> {
+    let at = |position: Option<(usize, usize)>, message: String| Diagnostic {
+        file: file.to_string(),
+        position,
+        message,
+    };
+
+    let prepared = match renderer.prepare(ch) {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let position = e.downcast_ref::<ParseError>().and_then(|p| p.line.zip(p.column));
+            return vec![at(position, format!("{:#}", e))];
+        }
+    };
+    let template = match Template::compile(&prepared.template) {
+        Ok(template) => template,
+        Err(e) => return vec![at(e.pos(), format!("syntax error: {}", e.reason()))],
+    };
+
+    let mut lint = Lint::new(renderer);
+    lint.collect_inline_partials(&template);
+    lint.walk(&template);
+    let mut diagnostics: Vec<Diagnostic> = lint
+        .unknown_helpers
+        .iter()
+        .map(|(name, position)| at(Some(*position), format!("unknown helper `{}`", name)))
+        .chain(
+            lint.missing_partials
+                .iter()
+                .map(|(name, position)| at(Some(*position), format!("missing partial `{}`", name))),
+        )
+        .collect();
+
+    // Stand-ins for the unknown helpers and missing partials already reported, so that rendering
+    // gets past them to the undefined variables.
+    let mut hbs = render::strict_copy(renderer.handlebars());
+    for (name, _) in &lint.unknown_helpers {
+        hbs.register_helper(name, Box::new(Stub));
+    }
+    for (name, _) in &lint.missing_partials {
+        hbs.register_template(name, Template::default());
+    }
+    let search = strict::find_missing(&hbs, &prepared.template, &prepared.context);
+    diagnostics.extend(
+        search
+            .missing
+            .iter()
+            .filter(|m| !renderer.is_allowed_missing(m))
+            .map(|m| at(Some((m.line, m.column)), m.message())),
+    );
+    diagnostics.extend(search.error.map(|e| render_error(file, e)));
+    diagnostics.sort_by_key(|d| d.position);
+    if search.truncated {
+        diagnostics.push(at(None, strict::Search::truncated_message()));
+    }
+    diagnostics
+}
+
+/// A render error, attributed to the partial it occurred in if any.
+fn render_error(file: &str, e: RenderError) -> Diagnostic {
+    Diagnostic {
+        file: e.template_name.clone().unwrap_or_else(|| file.to_string()),
+        position: e.line_no.zip(e.column_no),
+        message: match e.reason() {
+            RenderErrorReason::MissingVariable(Some(path)) => format!("undefined variable `{}`", path),
+            reason => reason.to_string(),
+        },
+    }
+}
+
+/// Stands in for an unknown helper, giving null.
+struct Stub;
+
+impl HelperDef for Stub {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        _: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        Ok(ScopedJson::Derived(serde_json::Value::Null))
+    }
+}
+
+/// Walks a compiled chapter looking for helpers and partials that do not exist.
+struct Lint<'a> {
+    hbs: &'a Handlebars<'static>,
+    known_helpers: BTreeSet<&'static str>,
+    /// Partials defined within the chapter with `{{#*inline "name"}}`.
+    inline_partials: BTreeSet<String>,
+    unknown_helpers: Vec<(String, (usize, usize))>,
+    missing_partials: Vec<(String, (usize, usize))>,
+}
+
+impl<'a> Lint<'a> {
+    fn new(renderer: &'a Renderer) -> Lint<'a> {
+        Lint {
+            hbs: renderer.handlebars(),
+            known_helpers: renderer.helper_names(),
+            inline_partials: BTreeSet::new(),
+            unknown_helpers: Vec::new(),
+            missing_partials: Vec::new(),
+        }
+    }
+
+    fn collect_inline_partials(&mut self, template: &Template) {
+        for element in &template.elements {
+            match element {
+                TemplateElement::DecoratorBlock(d) | TemplateElement::DecoratorExpression(d) => {
+                    if d.name.as_name() == Some("inline") {
+                        if let Some(Parameter::Literal(serde_json::Value::String(name))) = d.params.first() {
+                            self.inline_partials.insert(name.clone());
+                        }
+                    }
+                    if let Some(t) = &d.template {
+                        self.collect_inline_partials(t);
+                    }
+                }
+                TemplateElement::HelperBlock(h) => {
+                    for t in h.template.iter().chain(&h.inverse) {
+                        self.collect_inline_partials(t);
+                    }
+                }
+                TemplateElement::PartialBlock(d) => {
+                    if let Some(t) = &d.template {
+                        self.collect_inline_partials(t);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn walk(&mut self, template: &Template) {
+        for (element, TemplateMapping(line, column)) in template.elements.iter().zip(&template.mapping) {
+            let position = (*line, *column);
+            match element {
+                TemplateElement::Expression(h) | TemplateElement::HtmlExpression(h) | TemplateElement::HelperBlock(h) => {
+                    // Without parameters, `{{name}}` and `{{#name}}` may just as well refer to a variable.
+                    if !h.params.is_empty() || !h.hash.is_empty() {
+                        if let Some(name) = h.name.as_name() {
+                            self.helper(name, position);
+                        }
+                    }
+                    self.params(h.params.iter().chain(h.hash.values()), position);
+                    for t in h.template.iter().chain(&h.inverse) {
+                        self.walk(t);
+                    }
+                }
+                TemplateElement::PartialExpression(d) | TemplateElement::PartialBlock(d) => {
+                    // A partial block renders its own content when the partial is missing.
+                    let is_block = matches!(element, TemplateElement::PartialBlock(_));
+                    if let Some(name) = d.name.as_name() {
+                        let exists = name.starts_with('@') || self.inline_partials.contains(name) || self.hbs.has_template(name);
+                        if !exists && !is_block && !self.missing_partials.iter().any(|(n, _)| n == name) {
+                            self.missing_partials.push((name.to_string(), position));
+                        }
+                    }
+                    self.params(d.params.iter().chain(d.hash.values()), position);
+                    if let Some(t) = &d.template {
+                        self.walk(t);
+                    }
+                }
+                TemplateElement::DecoratorExpression(d) | TemplateElement::DecoratorBlock(d) => {
+                    self.params(d.params.iter().chain(d.hash.values()), position);
+                    if let Some(t) = &d.template {
+                        self.walk(t);
+                    }
+                }
+                TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+            }
+        }
+    }
+
+    /// Checks the subexpressions among `params`.
+    fn params<'p>(&mut self, params: impl IntoIterator<Item = &'p Parameter>, position: (usize, usize)) {
+        for param in params {
+            if let Parameter::Subexpression(sub) = param {
+                if sub.is_helper() {
+                    self.helper(sub.name(), position);
+                }
+                let nested: Vec<&Parameter> = sub
+                    .params()
+                    .into_iter()
+                    .flatten()
+                    .chain(sub.hash().into_iter().flat_map(|h| h.values()))
+                    .collect();
+                self.params(nested, position);
+            }
+        }
+    }
+
+    fn helper(&mut self, name: &str, position: (usize, usize)) {
+        if !self.known_helpers.contains(name) && !self.unknown_helpers.iter().any(|(n, _)| n == name) {
+            self.unknown_helpers.push((name.to_string(), position));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn check(settings: &str, content: &str) -> Vec<String> {
+        let config = mdbook::Config::from_str(&format!("[preprocessor.template]\n{}", settings)).unwrap();
+        let renderer = Renderer::new(&config, Path::new("."), "html").unwrap();
+        let ch = Chapter::new("a", content.to_string(), "a.md", Vec::new());
+        check_chapter(&renderer, &ch, "src/a.md").iter().map(Diagnostic::to_string).collect()
+    }
+
+    #[test]
+    fn reports_unknown_helpers_once() {
+        let content = "{{shout renderer}}\n{{#each book.authors}}{{shout (sort-em this)}}{{/each}}\n{{len \"ab\"}}";
+        assert_eq!(
+            check("", content),
+            ["src/a.md:1:1: unknown helper `shout`", "src/a.md:2:23: unknown helper `sort-em`"]
+        );
+        // With helpers off, the library is unknown too.
+        assert_eq!(check("", "{{upper renderer}}"), ["src/a.md:1:1: unknown helper `upper`"]);
+    }
+
+    #[test]
+    fn bare_names_may_be_variables() {
+        assert_eq!(check("", "{{shout}}"), ["src/a.md:1:1: undefined variable `shout` in `{{shout}}`"]);
+        assert!(check("", "{{renderer}}").is_empty());
+    }
+
+    #[test]
+    fn reports_missing_partials_but_not_inline_ones_or_blocks() {
+        let content = "{{#*inline \"note\"}}n{{/inline}}\n{{#if renderer}}{{#*inline \"deep\"}}d{{/inline}}{{/if}}\n\
+                       {{> note}} {{> deep}} {{> missing}}\n{{#> fallback}}shown{{/fallback}} {{> missing}}";
+        assert_eq!(check("", content), ["src/a.md:3:23: missing partial `missing`"]);
+    }
+
+    #[test]
+    fn reports_syntax_errors_at_their_position() {
+        let diagnostics = check("", "line\n{{#if x}}\n{{/each}}");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].starts_with("src/a.md:3:"), "{}", diagnostics[0]);
+        assert!(diagnostics[0].contains("syntax error"), "{}", diagnostics[0]);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::delimiters::Delimiters;
+use crate::escape::Escape;
+use crate::merge::{self, ArrayStrategy, Strategy};
+use mdbook::errors::Error;
+use serde::Deserialize;
+
+/// Settings read from the `[preprocessor.template]` table of `book.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    /// Data files merged into the template context, in order.
+    #[serde(default)]
+    pub paths: Vec<DataSource>,
+    /// Mount every data file without an explicit `as` under its file name, minus the extension.
+    #[serde(default)]
+    pub namespace_by_filename: bool,
+    /// Mount files found through a directory or glob entry under keys built from their path.
+    #[serde(default)]
+    pub namespace_by_path: bool,
+    /// What to do with a data file whose root is not an object and that has no key to mount it under.
+    #[serde(default)]
+    pub non_object_root: NonObjectRoot,
+    /// Directory that relative `paths` are resolved against, relative to the book root.
+    #[serde(default)]
+    pub data_root: Option<String>,
+    /// How each data file is combined with the ones before it.
+    #[serde(default)]
+    pub merge: Strategy,
+    /// How arrays at the same path are combined under deep merging.
+    #[serde(default)]
+    pub array_merge: ArrayStrategy,
+    /// Field identifying array items under `array-merge = "merge-by-key"`.
+    #[serde(default = "default_merge_key")]
+    pub merge_key: String,
+    /// What to do with a chapter that fails to render; see [`Config::on_error`] for the default.
+    #[serde(default)]
+    pub on_error: Option<OnError>,
+    /// Reject chapters that reference variables missing from the context. Unless `on-error` says
+    /// otherwise, this fails the build.
+    #[serde(default)]
+    pub strict: bool,
+    /// Glob patterns (`*` matches one dotted segment, `**` any number) for variables that may be
+    /// missing in strict mode.
+    #[serde(default)]
+    pub allow_missing: Vec<String>,
+    /// How substituted values are escaped.
+    #[serde(default)]
+    pub escape: Escape,
+    /// Read a leading `---` YAML block of each chapter as page variables. They are available as
+    /// `page.*` and are also deep-merged over the data context, taking precedence over data files.
+    /// Off by default, since a chapter may open with a thematic break.
+    #[serde(default)]
+    pub front_matter: bool,
+    /// Leave code blocks and inline code untouched. A fenced block can opt back in with the
+    /// `template` attribute, e.g. ```` ```rust,template ````.
+    #[serde(default = "default_true")]
+    pub skip_code: bool,
+    /// Regexes for text passed through without templating. Defaults to GitHub Actions-style
+    /// `${{ ... }}` expressions.
+    #[serde(default = "default_protect")]
+    pub protect: Vec<String>,
+    /// Opening and closing delimiters used in place of `{{` and `}}`, e.g. `["[[", "]]"]`. Any
+    /// literal `{{` in a chapter is then left as it is.
+    #[serde(default)]
+    pub delimiters: Option<Delimiters>,
+    /// Directory of `*.hbs` and `*.md` partials, relative to the book root, each registered under
+    /// its relative path without the extension, e.g. `{{> callouts/rpc-warning}}`.
+    #[serde(default)]
+    pub partials: Option<String>,
+    /// Register the built-in helper library (case conversion, arithmetic, `join`, `default`, ...).
+    /// Requires the `helpers` cargo feature, which is on by default.
+    #[serde(default)]
+    pub helpers: bool,
+    /// Warn during the build about data keys no chapter references and references to keys that
+    /// do not exist, as reported by `mdbook-template keys`.
+    #[serde(default)]
+    pub report_keys: bool,
+}
+
+/// One entry of `paths`: either a plain path string or a table such as
+/// `{ path = "assets/portals.json", as = "portals", schema = "schemas/portals.schema.json" }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "PathEntry")]
+pub struct DataSource {
+    pub path: String,
+    /// Dotted key the file's content is mounted under, e.g. `portals` or `networks.testnet`.
+    pub key: Option<String>,
+    /// JSON Schema every file of the entry is validated against, resolved like `path`. Schemas using
+    /// keywords the validator does not support are rejected, see [`crate::schema::validate`].
+    pub schema: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "a path string or a table with `path` and optionally `as` and `schema`"
+)]
+enum PathEntry {
+    Path(String),
+    Table {
+        path: String,
+        #[serde(rename = "as")]
+        key: Option<String>,
+        schema: Option<String>,
+    },
+}
+
+impl From<PathEntry> for DataSource {
+    fn from(entry: PathEntry) -> DataSource {
+        match entry {
+            PathEntry::Path(path) => DataSource {
+                path,
+                key: None,
+                schema: None,
+            },
+            PathEntry::Table { path, key, schema } => DataSource { path, key, schema },
+        }
+    }
+}
+
+/// Policy for data files whose top-level value is an array or scalar.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NonObjectRoot {
+    /// Mount the value under the file name, minus the extension.
+    #[default]
+    Mount,
+    /// Fail the build.
+    Error,
+}
+
+/// Policy applied when a chapter fails to render.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+    /// Collect every failing chapter and fail the build.
+    Fail,
+    /// Log a warning and render the chapter anyway if only the `strict` check failed, with the
+    /// undefined values left empty; otherwise leave the chapter source untouched.
+    Warn,
+    /// Log a warning and leave the chapter source untouched, even if only the `strict` check failed.
+    KeepSource,
+    /// Log the error and replace the chapter content with an empty string.
+    Blank,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_protect() -> Vec<String> {
+    vec![r"\$\{\{.*?\}\}".to_string()]
+}
+
+fn default_merge_key() -> String {
+    "name".to_string()
+}
+
+impl Config {
+    /// The `on-error` policy: as configured, or `fail` under `strict` and `warn` otherwise, so that
+    /// strict mode does not ship chapters with undefined variables.
+    pub fn on_error(&self) -> OnError {
+        self.on_error.unwrap_or(if self.strict { OnError::Fail } else { OnError::Warn })
+    }
+
+    pub fn merge_options(&self) -> merge::Options {
+        merge::Options {
+            strategy: self.merge,
+            arrays: self.array_merge,
+            key: self.merge_key.clone(),
+        }
+    }
+
+    pub fn from_book_config(config: &mdbook::Config) -> Result<Config, Error> {
+        let table = config
+            .get("preprocessor.template")
+            .ok_or_else(|| Error::msg("missing [preprocessor.template] config"))?;
+
+        table
+            .clone()
+            .try_into()
+            .map_err(|e| Error::msg(format!("invalid [preprocessor.template] config: {}", e)))
+    }
+}
@@ -0,0 +1,419 @@
+use crate::config::{Config, DataSource, NonObjectRoot};
+use crate::format::Format;
+use crate::glob;
+use crate::merge::{self, Origins};
+use crate::schema;
+use anyhow::Context as AnyhowContext;
+use log::warn;
+use mdbook::errors::Error;
+use serde_json::Value as Json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A single data file produced by expanding a `paths` entry.
+struct DataFile {
+    /// The path as configured, or for globs and directories the matched path under the entry.
+    path: String,
+    resolved: PathBuf,
+    /// Key path the content is mounted under, if any. Names taken from files and directories are
+    /// single segments even when they contain dots.
+    key: Option<Vec<String>>,
+}
+
+/// Loads every file listed in `paths` and merges them into a single template context, along with
+/// the file each value came from.
+///
+/// Entries may be files, directories or glob patterns; the latter two expand to the data files they
+/// contain in sorted order. Each file is parsed according to its extension (see [`Format`]),
+/// mounted under its key (see [`expand`]), or under its file stem if it holds an array or scalar
+/// and `non-object-root` allows it, and merged into the files before it using the configured
+/// `merge` strategy. Files of an entry with a `schema` are validated against it first; all
+/// violations are reported together and fail the load.
+///
+/// Relative paths are resolved against `data-root`, which is itself relative to the book `root`
+/// and defaults to it.
+pub fn load(cfg: &Config, root: &Path) -> Result<(Json, Origins), Error> {
+    let data_root = data_root(cfg, root);
+
+    let opts = cfg.merge_options();
+    let mut origins = Origins::default();
+    let mut context = Json::Object(serde_json::Map::new());
+    // Schema violations of every file, reported together once all files are read.
+    let mut violations = Vec::new();
+    for source in &cfg.paths {
+        let schema = source.schema.as_deref().map(|s| load_schema(&data_root, s)).transpose()?;
+        for file in expand(cfg, &data_root, source)? {
+            let DataFile { path, resolved, key } = file;
+            let txt = fs::read_to_string(&resolved)
+                .with_context(|| format!("reading {} (resolved to {})", path, resolved.display()))?;
+            let val = Format::from_path(&resolved)
+                .parse(&txt)
+                .with_context(|| format!("parsing {} (resolved to {})", path, resolved.display()))?;
+            if let Some(schema) = &schema {
+                violations.extend(schema::validate(schema, &val).into_iter().map(|v| format!("{}: {}", path, v)));
+            }
+
+            let key = match key {
+                None if !val.is_object() => match cfg.non_object_root {
+                    NonObjectRoot::Mount => file_stem(&resolved).map(|stem| vec![stem]),
+                    NonObjectRoot::Error => {
+                        return Err(Error::msg(format!(
+                            "{} (resolved to {}) holds {} at the top level; give its `paths` entry an `as` key \
+                             or set `non-object-root = \"mount\"`",
+                            path,
+                            resolved.display(),
+                            describe(&val)
+                        )))
+                    }
+                },
+                key => key,
+            };
+            let key: Vec<&str> = key.iter().flatten().map(String::as_str).collect();
+            merge::merge(&mut context, &key, val, &path, &opts, &mut origins)?;
+        }
+    }
+
+    if !violations.is_empty() {
+        return Err(Error::msg(format!(
+            "{} schema violation(s) in data files:\n  {}",
+            violations.len(),
+            violations.join("\n  ")
+        )));
+    }
+    Ok((context, origins))
+}
+
+/// The directory relative data paths are resolved against: `data-root` within the book `root`.
+pub fn data_root(cfg: &Config, root: &Path) -> PathBuf {
+    match &cfg.data_root {
+        Some(dir) => root.join(dir),
+        None => root.to_path_buf(),
+    }
+}
+
+/// Reads the JSON Schema at `path`, which may be written in any of the data formats, making sure it
+/// only uses what [`schema::validate`] checks.
+fn load_schema(data_root: &Path, path: &str) -> Result<Json, Error> {
+    let resolved = resolve(data_root, path);
+    let txt = fs::read_to_string(&resolved)
+        .with_context(|| format!("reading schema {} (resolved to {})", path, resolved.display()))?;
+    let schema = Format::from_path(&resolved)
+        .parse(&txt)
+        .with_context(|| format!("parsing schema {} (resolved to {})", path, resolved.display()))?;
+    let unsupported = schema::unsupported(&schema);
+    if !unsupported.is_empty() {
+        return Err(Error::msg(format!(
+            "schema {} (resolved to {}) cannot be checked:\n  {}",
+            path,
+            resolved.display(),
+            unsupported.join("\n  ")
+        )));
+    }
+    Ok(schema)
+}
+
+/// Expands one `paths` entry into the data files it names and the key each one is mounted under.
+///
+/// Files are mounted under the entry's `as` key if given, otherwise under their file stem with
+/// `namespace-by-filename` or `namespace-by-path`. With `namespace-by-path`, files found through a directory or glob are
+/// mounted under the directory's name (or `as`) followed by their path below it, so
+/// `assets/partners/eu/foo.json` matched by `assets/partners/**/*.json` becomes `partners.eu.foo`.
+fn expand(cfg: &Config, data_root: &Path, source: &DataSource) -> Result<Vec<DataFile>, Error> {
+    let (base, pattern) = split_glob(&source.path);
+    let resolved_base = resolve(data_root, &base);
+
+    if pattern.is_none() && !resolved_base.is_dir() {
+        let key = match &source.key {
+            Some(key) => Some(dotted(key)),
+            None if cfg.namespace_by_filename || cfg.namespace_by_path => file_stem(&resolved_base).map(|stem| vec![stem]),
+            None => None,
+        };
+        return Ok(vec![DataFile {
+            path: source.path.clone(),
+            resolved: resolved_base,
+            key,
+        }]);
+    }
+
+    if !resolved_base.is_dir() {
+        warn!("no data files found for {} ({} is not a directory)", source.path, resolved_base.display());
+        return Ok(Vec::new());
+    }
+
+    let matcher = pattern.as_deref().map(|p| glob::to_regex(p, '/')).transpose()?;
+    let mut files = Vec::new();
+    for entry in WalkDir::new(&resolved_base).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("reading {} (resolved to {})", base, resolved_base.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(&resolved_base).unwrap_or(entry.path());
+        let rel_str = rel.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        let matched = match &matcher {
+            Some(re) => re.is_match(&rel_str),
+            None => Format::from_extension(entry.path()).is_some(),
+        };
+        if !matched {
+            continue;
+        }
+
+        let key = if cfg.namespace_by_path {
+            let mut segments = match &source.key {
+                Some(key) => dotted(key),
+                None => resolved_base
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .into_iter()
+                    .collect(),
+            };
+            if let Some(parent) = rel.parent() {
+                segments.extend(parent.components().map(|c| c.as_os_str().to_string_lossy().into_owned()));
+            }
+            segments.extend(file_stem(rel));
+            Some(segments)
+        } else if let Some(key) = &source.key {
+            Some(dotted(key))
+        } else if cfg.namespace_by_filename {
+            file_stem(rel).map(|stem| vec![stem])
+        } else {
+            None
+        };
+
+        files.push(DataFile {
+            path: if base.is_empty() { rel_str } else { format!("{}/{}", base, rel_str) },
+            resolved: entry.path().to_path_buf(),
+            key,
+        });
+    }
+
+    if files.is_empty() {
+        warn!("no data files found for {} (resolved to {})", source.path, resolved_base.display());
+    }
+    Ok(files)
+}
+
+/// Splits a `paths` entry into the directory before its first glob segment and the pattern for the
+/// rest, e.g. `assets/partners/*.json` into `assets/partners` and `*.json`.
+fn split_glob(path: &str) -> (String, Option<String>) {
+    let segments: Vec<&str> = path.split('/').collect();
+    match segments.iter().position(|s| s.contains(['*', '?'])) {
+        Some(idx) => (segments[..idx].join("/"), Some(segments[idx..].join("/"))),
+        None => (path.to_string(), None),
+    }
+}
+
+/// Names the kind of a JSON value for error messages.
+fn describe(value: &Json) -> &'static str {
+    match value {
+        Json::Null => "null",
+        Json::Bool(_) => "a boolean",
+        Json::Number(_) => "a number",
+        Json::String(_) => "a string",
+        Json::Array(_) => "an array",
+        Json::Object(_) => "an object",
+    }
+}
+
+/// Splits a dotted `as` key into its segments.
+fn dotted(key: &str) -> Vec<String> {
+    key.split('.').map(str::to_string).collect()
+}
+
+/// The file name of `path` without its extension.
+fn file_stem(path: &Path) -> Option<String> {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+}
+
+/// Joins `path` onto `base` and makes the result absolute so error messages are unambiguous.
+pub fn resolve(base: &Path, path: &str) -> PathBuf {
+    let joined = base.join(path);
+    std::path::absolute(&joined).unwrap_or(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A fresh directory holding `files`, named after the test.
+    fn book(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mdbook-template-data-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(settings: &str) -> Config {
+        toml::from_str(settings).unwrap()
+    }
+
+    #[test]
+    fn data_root_is_relative_to_the_book_root() {
+        let root = Path::new("/books/docs");
+        assert_eq!(data_root(&config(""), root), root);
+        assert_eq!(data_root(&config(r#"data-root = "assets""#), root), root.join("assets"));
+    }
+
+    #[test]
+    fn resolve_gives_absolute_paths() {
+        let resolved = resolve(Path::new("book"), "assets/a.json");
+        assert!(resolved.is_absolute());
+        assert!(resolved.ends_with("book/assets/a.json"));
+        assert_eq!(resolve(Path::new("book"), "/etc/a.json"), Path::new("/etc/a.json"));
+    }
+
+    #[test]
+    fn loads_paths_relative_to_data_root() {
+        let root = book("data-root", &[("assets/a.json", r#"{"a": 1}"#), ("b.json", r#"{"b": 2}"#)]);
+        let (context, _) = load(&config("paths = [\"a.json\"]\ndata-root = \"assets\""), &root).unwrap();
+        assert_eq!(context, json!({ "a": 1 }));
+
+        let (context, _) = load(&config(r#"paths = ["b.json", "assets/a.json"]"#), &root).unwrap();
+        assert_eq!(context, json!({ "a": 1, "b": 2 }));
+
+        let err = load(&config("paths = [\"b.json\"]\ndata-root = \"assets\""), &root).unwrap_err();
+        assert!(format!("{:#}", err).contains(&root.join("assets/b.json").display().to_string()), "{:#}", err);
+    }
+
+    #[test]
+    fn mounts_files_under_a_dotted_as_key() {
+        let root = book("as-key", &[("testnet.json", r#"{"url": "t"}"#), ("portals.json", r#"{"sui": "s"}"#)]);
+        let settings = r#"paths = [{ path = "testnet.json", as = "networks.testnet" }, "portals.json"]"#;
+        let (context, origins) = load(&config(settings), &root).unwrap();
+        assert_eq!(context, json!({ "networks": { "testnet": { "url": "t" } }, "sui": "s" }));
+        assert_eq!(origins.get("/networks/testnet/url"), Some("testnet.json"));
+    }
+
+    #[test]
+    fn namespace_by_filename_mounts_files_without_as_under_their_stem() {
+        let root = book("by-filename", &[("portals.json", r#"{"sui": "s"}"#), ("ops.v2.yaml", "a: 1")]);
+        let settings = r#"namespace-by-filename = true
+paths = ["portals.json", "ops.v2.yaml", { path = "portals.json", as = "p" }]"#;
+        let (context, _) = load(&config(settings), &root).unwrap();
+        assert_eq!(
+            context,
+            json!({ "portals": { "sui": "s" }, "ops.v2": { "a": 1 }, "p": { "sui": "s" } })
+        );
+    }
+
+    #[test]
+    fn mounts_non_object_files_under_their_stem_by_default() {
+        let root = book("non-object", &[("regions.yaml", "- eu\n- us\n"), ("version.json", "3")]);
+        let (context, origins) = load(&config(r#"paths = ["regions.yaml", "version.json"]"#), &root).unwrap();
+        assert_eq!(context, json!({ "regions": ["eu", "us"], "version": 3 }));
+        assert_eq!(origins.get("/regions/0"), Some("regions.yaml"));
+
+        // An `as` key mounts them anywhere, whatever `non-object-root` says.
+        let settings = "non-object-root = \"error\"\npaths = [{ path = \"version.json\", as = \"meta.v\" }]";
+        let (context, _) = load(&config(settings), &root).unwrap();
+        assert_eq!(context, json!({ "meta": { "v": 3 } }));
+    }
+
+    #[test]
+    fn non_object_root_error_rejects_arrays_and_scalars() {
+        let root = book("non-object-error", &[("regions.yaml", "- eu\n")]);
+        let settings = "non-object-root = \"error\"\npaths = [\"regions.yaml\"]";
+        let err = load(&config(settings), &root).unwrap_err().to_string();
+        assert!(err.starts_with("regions.yaml (resolved to "), "{}", err);
+        assert!(err.contains("holds an array at the top level"), "{}", err);
+        assert!(err.contains("`non-object-root = \"mount\"`"), "{}", err);
+    }
+
+    #[test]
+    fn validates_files_against_their_schema() {
+        let root = book(
+            "schema",
+            &[
+                ("ops.json", r#"{"port": "80", "extra": 1}"#),
+                ("ops.schema.yaml", "properties:\n  port: { type: integer }\nadditionalProperties: false\n"),
+                ("loose.schema.json", r#"{"properties": {"port": {"contains": {}}}}"#),
+            ],
+        );
+        let err = load(&config(r#"paths = [{ path = "ops.json", schema = "ops.schema.yaml" }]"#), &root).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "2 schema violation(s) in data files:\n  \
+             ops.json: /extra: is not an allowed property\n  \
+             ops.json: /port: expected integer, found string"
+        );
+
+        let err = load(&config(r#"paths = [{ path = "ops.json", schema = "loose.schema.json" }]"#), &root).unwrap_err();
+        let message = err.to_string();
+        assert!(message.starts_with("schema loose.schema.json (resolved to "), "{}", message);
+        assert!(
+            message.ends_with("cannot be checked:\n  `#/properties/port` uses `contains`, which is not supported"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn splits_entries_at_the_first_glob_segment() {
+        assert_eq!(split_glob("assets/partners/*.json"), ("assets/partners".to_string(), Some("*.json".to_string())));
+        assert_eq!(split_glob("assets/**/x/*.json"), ("assets".to_string(), Some("**/x/*.json".to_string())));
+        assert_eq!(split_glob("*.yaml"), (String::new(), Some("*.yaml".to_string())));
+        assert_eq!(split_glob("assets/a.json"), ("assets/a.json".to_string(), None));
+    }
+
+    fn expanded(root: &Path, settings: &str) -> Vec<(String, Option<Vec<String>>)> {
+        let cfg = config(settings);
+        expand(&cfg, root, &cfg.paths[0])
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.path, f.key))
+            .collect()
+    }
+
+    #[test]
+    fn expands_globs_and_directories_in_sorted_order() {
+        let root = book(
+            "expand",
+            &[
+                ("partners/top.json", "{}"),
+                ("partners/eu/b.json", "{}"),
+                ("partners/eu/a.yaml", "{}"),
+                ("partners/eu/notes.txt", ""),
+            ],
+        );
+        let paths = |settings: &str| expanded(&root, settings).into_iter().map(|(p, _)| p).collect::<Vec<_>>();
+
+        // `**/` also matches no directory at all.
+        assert_eq!(
+            paths(r#"paths = ["partners/**/*.json"]"#),
+            ["partners/eu/b.json", "partners/top.json"]
+        );
+        assert_eq!(paths(r#"paths = ["partners/*.json"]"#), ["partners/top.json"]);
+        // Directories take every file in a data format.
+        assert_eq!(
+            paths(r#"paths = ["partners"]"#),
+            ["partners/eu/a.yaml", "partners/eu/b.json", "partners/top.json"]
+        );
+        assert!(paths(r#"paths = ["partners/*.toml"]"#).is_empty());
+        assert!(paths(r#"paths = ["missing/*.json"]"#).is_empty());
+    }
+
+    #[test]
+    fn namespace_by_path_keeps_dots_in_names() {
+        let root = book("by-path", &[("v1.2/sub.d/z.json", "{}"), ("v1.2/ops.v2.json", "{}")]);
+        let keys = expanded(&root, "namespace-by-path = true\npaths = [\"v1.2\"]");
+        assert_eq!(
+            keys,
+            [
+                ("v1.2/ops.v2.json".to_string(), Some(vec!["v1.2".to_string(), "ops.v2".to_string()])),
+                ("v1.2/sub.d/z.json".to_string(), Some(vec!["v1.2".to_string(), "sub.d".to_string(), "z".to_string()])),
+            ]
+        );
+
+        let keys = expanded(&root, "namespace-by-path = true\npaths = [{ path = \"v1.2/**/*.json\", as = \"a.b\" }]");
+        assert_eq!(keys[1].1, Some(vec!["a".to_string(), "b".to_string(), "sub.d".to_string(), "z".to_string()]));
+
+        let (context, _) = load(&config("namespace-by-path = true\npaths = [\"v1.2\"]"), &root).unwrap();
+        assert_eq!(context, json!({ "v1.2": { "ops.v2": {}, "sub.d": { "z": {} } } }));
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Deserialize;
+
+/// Custom expression delimiters, e.g. `delimiters = ["[[", "]]"]`, translated to Handlebars' `{{ }}`
+/// before rendering.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "[String; 2]")]
+pub struct Delimiters {
+    pub open: String,
+    pub close: String,
+}
+
+impl TryFrom<[String; 2]> for Delimiters {
+    type Error = String;
+
+    fn try_from([open, close]: [String; 2]) -> Result<Delimiters, String> {
+        if open.is_empty() || close.is_empty() {
+            return Err("delimiters must not be empty".to_string());
+        }
+        if open.contains("{{") || close.contains("}}") {
+            return Err(format!("delimiters {:?} and {:?} overlap with Handlebars' own", open, close));
+        }
+        Ok(Delimiters { open, close })
+    }
+}
+
+impl Delimiters {
+    /// Rewrites every `open ... close` pair in `content` as `{{ ... }}`, leaving the text between
+    /// the delimiters as it is, so `[[#if x]]` becomes `{{#if x}}` and `[[{raw}]]` becomes
+    /// `{{{raw}}}`. A backslash before `open` yields a literal `open`, and an `open` with no `close`
+    /// after it is left alone.
+    ///
+    /// Literal `{{` in `content` must be protected beforehand, or Handlebars will still see it.
+    pub fn translate(&self, content: &str) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut rest = content;
+        while let Some(start) = rest.find(&self.open) {
+            let after = &rest[start + self.open.len()..];
+            if rest[..start].ends_with('\\') {
+                out.push_str(&rest[..start - 1]);
+                out.push_str(&self.open);
+                rest = after;
+                continue;
+            }
+            let Some(end) = after.find(&self.close) else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            out.push_str("{{");
+            out.push_str(&after[..end]);
+            out.push_str("}}");
+            rest = &after[end + self.close.len()..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brackets() -> Delimiters {
+        Delimiters::try_from(["[[".to_string(), "]]".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn translates_delimited_expressions() {
+        let d = brackets();
+        assert_eq!(d.translate("[[#if x]]a[[/if]]"), "{{#if x}}a{{/if}}");
+        assert_eq!(d.translate("[[{raw}]] [[ name ]]"), "{{{raw}}} {{ name }}");
+        assert_eq!(d.translate("no tags"), "no tags");
+    }
+
+    #[test]
+    fn escaped_and_unclosed_delimiters_stay_literal() {
+        let d = brackets();
+        assert_eq!(d.translate(r"\[[x]] [[y]]"), "[[x]] {{y}}");
+        assert_eq!(d.translate("[[x]] [[y"), "{{x}} [[y");
+        assert_eq!(d.translate("a]] [[b"), "a]] [[b");
+    }
+
+    #[test]
+    fn rejects_empty_or_overlapping_delimiters() {
+        let pair = |open: &str, close: &str| Delimiters::try_from([open.to_string(), close.to_string()]);
+        assert_eq!(pair("", "]]").unwrap_err(), "delimiters must not be empty");
+        assert!(pair("{{", "}}").unwrap_err().contains("overlap with Handlebars' own"));
+        assert!(pair("<%", "%>").is_ok());
+    }
+}
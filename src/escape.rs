@@ -0,0 +1,306 @@
+use handlebars::Handlebars;
+use serde::Deserialize;
+
+/// Marks the start of a value rendered under `escape = "markdown"`.
+const OPEN: char = '\u{F8F0}';
+/// Marks the end of a value rendered under `escape = "markdown"`.
+const CLOSE: char = '\u{F8F1}';
+
+/// How values substituted by `{{ ... }}` are escaped. `{{{ ... }}}` is never escaped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Escape {
+    /// Insert values verbatim.
+    #[default]
+    None,
+    /// Handlebars' default HTML entity escaping.
+    Html,
+    /// Escape only what Markdown would interpret at the place the value lands.
+    Markdown,
+}
+
+/// Installs the escape function for `mode` on `hbs`.
+pub fn register(hbs: &mut Handlebars, mode: Escape) {
+    match mode {
+        Escape::None => hbs.register_escape_fn(handlebars::no_escape),
+        Escape::Html => hbs.register_escape_fn(handlebars::html_escape),
+        // Where a value lands is only known once the whole chapter is rendered, so values are
+        // marked here and escaped by `finish`.
+        Escape::Markdown => hbs.register_escape_fn(|s| format!("{}{}{}", OPEN, s.replace([OPEN, CLOSE], ""), CLOSE)),
+    }
+}
+
+/// Escapes the values marked during rendering according to their surroundings and strips the marks.
+/// A no-op unless `mode` is `Escape::Markdown`.
+pub fn finish(rendered: String, mode: Escape) -> String {
+    if mode != Escape::Markdown || !rendered.contains(OPEN) {
+        return rendered;
+    }
+
+    let mut out = String::with_capacity(rendered.len());
+    let mut line_start = 0;
+    let mut fence: Option<String> = None;
+    let mut rest = rendered.as_str();
+
+    while let Some(c) = rest.chars().next() {
+        if c == OPEN {
+            let end = rest.find(CLOSE).unwrap_or(rest.len());
+            let value = &rest[OPEN.len_utf8()..end];
+            let place = if fence.is_some() {
+                Place::Code
+            } else {
+                Place::of(&out[line_start..])
+            };
+            out.push_str(&escape(value, place, is_table_row(&out[line_start..])));
+            rest = rest[end..].strip_prefix(CLOSE).unwrap_or("");
+            continue;
+        }
+
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+        if c == '\n' {
+            update_fence(&mut fence, &out[line_start..]);
+            line_start = out.len();
+        }
+    }
+
+    out
+}
+
+/// Escapes `value` for a Markdown table cell, turning line breaks into `<br>`.
+#[cfg(feature = "helpers")]
+pub fn table_cell(value: &str) -> String {
+    escape(value, Place::Text, true).replace('\n', "<br>")
+}
+
+/// Escapes `value` for the `( ... )` destination of a link inside a Markdown table.
+#[cfg(feature = "helpers")]
+pub fn table_link_destination(value: &str) -> String {
+    escape(value, Place::LinkDestination, true)
+}
+
+/// Where a value lands within a line of Markdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Place {
+    /// Inside a fenced code block or an inline code span.
+    Code,
+    /// Inside an HTML tag, e.g. an attribute value.
+    HtmlTag,
+    /// Inside the `( ... )` destination of a link or image.
+    LinkDestination,
+    /// At the start of a line, where block markers like `#`, `>` or `-` take effect.
+    LineStart,
+    /// Ordinary inline text.
+    Text,
+}
+
+impl Place {
+    /// Classifies the position right after `prefix`, the part of the current line already written.
+    fn of(prefix: &str) -> Place {
+        if prefix.trim().is_empty() {
+            return Place::LineStart;
+        }
+
+        let mut code_run = None;
+        let mut tag_open = false;
+        let mut link_depth = 0usize;
+        let mut prev = '\0';
+        let mut chars = prefix.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c == '`' {
+                let mut run = 1;
+                while chars.peek() == Some(&'`') {
+                    chars.next();
+                    run += 1;
+                }
+                code_run = match code_run {
+                    None => Some(run),
+                    Some(open) if open == run => None,
+                    open => open,
+                };
+                prev = '`';
+                continue;
+            }
+            if code_run.is_none() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '<' => tag_open = chars.peek().is_some_and(|n| n.is_ascii_alphabetic() || *n == '/'),
+                    '>' => tag_open = false,
+                    '(' if prev == ']' => link_depth = 1,
+                    '(' if link_depth > 0 => link_depth += 1,
+                    ')' if link_depth > 0 => link_depth -= 1,
+                    _ => {}
+                }
+            }
+            prev = c;
+        }
+
+        if code_run.is_some() {
+            Place::Code
+        } else if tag_open {
+            Place::HtmlTag
+        } else if link_depth > 0 {
+            Place::LinkDestination
+        } else {
+            Place::Text
+        }
+    }
+}
+
+/// Escapes `value` for `place`. Pipes are escaped everywhere in a table row, including code spans,
+/// since GFM splits cells before parsing inline content.
+fn escape(value: &str, place: Place, in_table: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+
+    for (i, c) in value.chars().enumerate() {
+        match place {
+            Place::Code => {}
+            Place::HtmlTag => {
+                match c {
+                    '"' => out.push_str("&quot;"),
+                    '\'' => out.push_str("&#39;"),
+                    '<' => out.push_str("&lt;"),
+                    '>' => out.push_str("&gt;"),
+                    _ if c == '|' && in_table => out.push_str("\\|"),
+                    _ => out.push(c),
+                }
+                continue;
+            }
+            Place::LinkDestination => {
+                if matches!(c, '(' | ')' | '\\') {
+                    out.push('\\');
+                }
+            }
+            Place::LineStart if i == 0 && matches!(c, '#' | '>' | '-' | '+' | '=') => out.push('\\'),
+            Place::LineStart | Place::Text => {
+                if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~') {
+                    out.push('\\');
+                }
+            }
+        }
+        if c == '|' && in_table {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+
+    if place == Place::LineStart {
+        escape_list_number(&mut out);
+    }
+    out
+}
+
+/// Keeps a value like `1. foo` at the start of a line from becoming an ordered list item.
+fn escape_list_number(value: &mut String) {
+    let digits = value.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && value[digits..].starts_with(['.', ')']) {
+        value.insert(digits, '\\');
+    }
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// Tracks whether the following lines are inside a fenced code block, given the line just completed.
+fn update_fence(fence: &mut Option<String>, line: &str) {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return;
+    }
+
+    let marker: String = trimmed.chars().take_while(|&c| c == '`' || c == '~').collect();
+    let is_fence = marker.len() >= 3 && marker.chars().all(|c| c == marker.chars().next().unwrap());
+    if !is_fence {
+        return;
+    }
+
+    match fence {
+        None => *fence = Some(marker),
+        Some(open) if marker.starts_with(open.as_str()) && trimmed[marker.len()..].trim().is_empty() => *fence = None,
+        Some(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn markdown(template: &str, value: &str) -> String {
+        let mut hbs = Handlebars::new();
+        register(&mut hbs, Escape::Markdown);
+        let rendered = hbs.render_template(template, &json!({ "v": value })).unwrap();
+        finish(rendered, Escape::Markdown)
+    }
+
+    #[test]
+    fn none_and_html_modes() {
+        let mut hbs = Handlebars::new();
+        register(&mut hbs, Escape::None);
+        assert_eq!(hbs.render_template("{{v}}", &json!({ "v": "<b>*" })).unwrap(), "<b>*");
+        register(&mut hbs, Escape::Html);
+        assert_eq!(hbs.render_template("{{v}}", &json!({ "v": "<b>" })).unwrap(), "&lt;b&gt;");
+    }
+
+    #[test]
+    fn escapes_inline_markup_in_text() {
+        assert_eq!(markdown("Name: {{v}}", "a_b *c* [d]"), "Name: a\\_b \\*c\\* \\[d\\]");
+        assert_eq!(markdown("Name: {{{v}}}", "a_b"), "Name: a_b");
+    }
+
+    #[test]
+    fn escapes_block_markers_at_line_start() {
+        assert_eq!(markdown("{{v}}", "# title"), "\\# title");
+        assert_eq!(markdown("  {{v}}", "> quote"), "  \\> quote");
+        assert_eq!(markdown("{{v}}", "1. item"), "1\\. item");
+        assert_eq!(markdown("x {{v}}", "# not a heading"), "x # not a heading");
+    }
+
+    #[test]
+    fn leaves_code_alone() {
+        assert_eq!(markdown("`{{v}}`", "a_b*"), "`a_b*`");
+        assert_eq!(markdown("```\n{{v}}\n```\n{{v}}", "*x*"), "```\n*x*\n```\n\\*x\\*");
+        assert_eq!(markdown("~~~~\n```\n{{v}}\n~~~~\n", "_"), "~~~~\n```\n_\n~~~~\n");
+    }
+
+    #[test]
+    fn escapes_html_attributes() {
+        assert_eq!(markdown("<a title=\"{{v}}\">", "\"x\" <y>"), "<a title=\"&quot;x&quot; &lt;y&gt;\">");
+    }
+
+    #[test]
+    fn escapes_link_destinations() {
+        assert_eq!(markdown("[x]({{v}})", "https://a.example/a(b)"), "[x](https://a.example/a\\(b\\))");
+    }
+
+    #[test]
+    fn escapes_pipes_in_table_rows() {
+        assert_eq!(markdown("| {{v}} |", "a|b"), "| a\\|b |");
+        assert_eq!(markdown("| `{{v}}` |", "a|b"), "| `a\\|b` |");
+        assert_eq!(markdown("not a row {{v}}", "a|b"), "not a row a|b");
+    }
+
+    #[test]
+    fn place_of_prefix() {
+        assert_eq!(Place::of("   "), Place::LineStart);
+        assert_eq!(Place::of("text `code"), Place::Code);
+        assert_eq!(Place::of("text ``a ` b"), Place::Code);
+        assert_eq!(Place::of("text `code` more"), Place::Text);
+        assert_eq!(Place::of("<img src=\""), Place::HtmlTag);
+        assert_eq!(Place::of("a < b"), Place::Text);
+        assert_eq!(Place::of("[x](a(b"), Place::LinkDestination);
+        assert_eq!(Place::of("[x](a) and"), Place::Text);
+        assert_eq!(Place::of("\\[x\\](a"), Place::Text);
+    }
+
+    #[cfg(feature = "helpers")]
+    #[test]
+    fn table_cells() {
+        assert_eq!(table_cell("a|b\nc*"), "a\\|b<br>c\\*");
+        assert_eq!(table_link_destination("x(1)|"), "x\\(1\\)\\|");
+    }
+}
@@ -0,0 +1,154 @@
+use regex::Regex;
+use serde_json::Value as Json;
+use std::fmt;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// The position some parsers append to their messages, reported separately instead.
+static POSITION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*at line \d+,? column \d+").unwrap());
+
+/// A data file format, picked from the file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Json5,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Picks the format from the extension of `path`. Unrecognised extensions are read as JSON.
+    pub fn from_path(path: &Path) -> Format {
+        Format::from_extension(path).unwrap_or(Format::Json)
+    }
+
+    /// Picks the format from the extension of `path`, if it is one of the recognised data formats.
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => Some(Format::Json),
+            Some("json5") => Some(Format::Json5),
+            Some("yaml") | Some("yml") => Some(Format::Yaml),
+            Some("toml") => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    /// Parses `text` into the JSON value used as template context.
+    pub fn parse(self, text: &str) -> Result<Json, ParseError> {
+        match self {
+            Format::Json => serde_json::from_str(text).map_err(|e| ParseError::new(Some((e.line(), e.column())), &e)),
+            Format::Json5 => json5::from_str(text).map_err(|e| {
+                let json5::Error::Message { location, .. } = &e;
+                ParseError::new(location.as_ref().map(|l| (l.line, l.column)), &e)
+            }),
+            Format::Yaml => serde_norway::from_str(text).map_err(|e| {
+                ParseError::new(e.location().map(|l| (l.line(), l.column())), &e)
+            }),
+            Format::Toml => text
+                .parse::<toml::Value>()
+                .map(toml_to_json)
+                .map_err(|e| ParseError::new(e.line_col().map(|(line, col)| (line + 1, col + 1)), &e)),
+        }
+    }
+}
+
+/// A syntax error in a data file, with the 1-based position reported by the parser when known.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(position: Option<(usize, usize)>, err: &dyn fmt::Display) -> ParseError {
+        // Parsers differ in whether and how they embed the position in their messages; pest-based
+        // ones (JSON5) render a source excerpt followed by `= <message>`.
+        let text = err.to_string();
+        let text = text
+            .lines()
+            .rev()
+            .find_map(|l| l.trim_start().strip_prefix("= "))
+            .unwrap_or(&text);
+        ParseError {
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, col)| col),
+            message: POSITION.replace(text, "").into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(col)) => write!(f, "line {}, column {}: {}", line, col, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Converts TOML into JSON, rendering datetimes as their TOML string form.
+fn toml_to_json(value: toml::Value) -> Json {
+    match value {
+        toml::Value::String(s) => Json::String(s),
+        toml::Value::Integer(i) => Json::from(i),
+        toml::Value::Float(f) => Json::from(f),
+        toml::Value::Boolean(b) => Json::Bool(b),
+        toml::Value::Datetime(d) => Json::String(d.to_string()),
+        toml::Value::Array(items) => Json::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Json::Object(table.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn position(format: Format, text: &str) -> (Option<usize>, Option<usize>) {
+        let err = format.parse(text).unwrap_err();
+        // The position is reported separately; others, like where a YAML sequence started, stay.
+        assert!(!err.message.contains(&format!("line {}", err.line.unwrap())), "{}", err.message);
+        (err.line, err.column)
+    }
+
+    #[test]
+    fn picks_the_format_from_the_extension() {
+        assert_eq!(Format::from_extension(Path::new("a/b.YML")), Some(Format::Yaml));
+        assert_eq!(Format::from_extension(Path::new("b.json5")), Some(Format::Json5));
+        assert_eq!(Format::from_extension(Path::new("b.txt")), None);
+        assert_eq!(Format::from_path(Path::new("b")), Format::Json);
+    }
+
+    #[test]
+    fn parses_every_format_to_the_same_value() {
+        let expected = json!({ "a": { "b": [1, "x"] } });
+        assert_eq!(Format::Json.parse(r#"{"a": {"b": [1, "x"]}}"#).unwrap(), expected);
+        assert_eq!(Format::Json5.parse("{a: {b: [1, 'x',],}, // c\n}").unwrap(), expected);
+        assert_eq!(Format::Yaml.parse("a:\n  b: [1, x]\n").unwrap(), expected);
+        assert_eq!(Format::Toml.parse("[a]\nb = [1, \"x\"]\n").unwrap(), expected);
+    }
+
+    #[test]
+    fn reports_one_based_error_positions() {
+        assert_eq!(position(Format::Json, "{\n  \"a\": ,\n}"), (Some(2), Some(8)));
+        assert_eq!(position(Format::Json5, "{\n  a: ,\n}"), (Some(2), Some(6)));
+        assert_eq!(position(Format::Yaml, "a: 1\nb: [1\n"), (Some(3), Some(1)));
+        // The TOML parser counts from zero.
+        assert_eq!(position(Format::Toml, "a = 1\nb = \n"), (Some(2), Some(5)));
+    }
+
+    #[test]
+    fn displays_the_position_before_the_message() {
+        let err = Format::Json.parse("[1,]").unwrap_err();
+        assert!(err.to_string().starts_with("line 1, column 4: "), "{}", err);
+    }
+
+    #[test]
+    fn renders_toml_datetimes_as_strings() {
+        let value = Format::Toml.parse("at = 1979-05-27T07:32:00Z").unwrap();
+        assert_eq!(value, json!({ "at": "1979-05-27T07:32:00Z" }));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::format::Format;
+use anyhow::Context as AnyhowContext;
+use mdbook::errors::Error;
+use serde_json::Value as Json;
+
+/// A chapter with its YAML front matter split off.
+pub struct Split {
+    /// The parsed front matter, always an object.
+    pub front_matter: Json,
+    /// The chapter with the front-matter block replaced by as many empty lines, so that line
+    /// numbers in template errors still match the source file.
+    pub body: String,
+    /// Number of lines the front-matter block occupied.
+    pub lines: usize,
+}
+
+/// Splits a `---`-delimited YAML block off the top of `content`.
+///
+/// Returns `None` if the chapter does not start with such a block, or if the block does not hold a
+/// mapping (a chapter opening with a thematic break followed by prose is not front matter).
+pub fn split(content: &str) -> Result<Option<Split>, Error> {
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return Ok(None);
+    };
+
+    let mut offset = 0;
+    let mut close = None;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            close = Some((offset, offset + line.len()));
+            break;
+        }
+        offset += line.len();
+    }
+    let Some((yaml_end, block_end)) = close else {
+        return Ok(None);
+    };
+
+    let yaml = &rest[..yaml_end];
+    let front_matter = if yaml.trim().is_empty() {
+        Json::Object(serde_json::Map::new())
+    } else {
+        match Format::Yaml.parse(yaml) {
+            Ok(value @ Json::Object(_)) => value,
+            Ok(_) => return Ok(None),
+            // Report lines relative to the chapter, which has the opening `---` on line 1.
+            Err(mut e) => {
+                e.line = e.line.map(|l| l + 1);
+                return Err(e).context("parsing front matter");
+            }
+        }
+    };
+
+    let lines = 1 + rest[..block_end].matches('\n').count();
+    Ok(Some(Split {
+        front_matter,
+        body: format!("{}{}", "\n".repeat(lines), &rest[block_end..]),
+        lines,
+    }))
+}
+
+/// Removes the empty lines that stood in for the front matter from a rendered chapter.
+pub fn strip_placeholder(rendered: String, lines: usize) -> String {
+    let leading = rendered.bytes().take(lines).take_while(|&b| b == b'\n').count();
+    rendered[leading..].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn splits_the_block_off_and_keeps_line_numbers() {
+        let block = split("---\ntitle: Intro\ntags: [a, b]\n---\n# {{title}}\n").unwrap().unwrap();
+        assert_eq!(block.front_matter, json!({ "title": "Intro", "tags": ["a", "b"] }));
+        assert_eq!(block.lines, 4);
+        assert_eq!(block.body, "\n\n\n\n# {{title}}\n");
+        assert_eq!(strip_placeholder(block.body, block.lines), "# {{title}}\n");
+
+        let block = split("---\n---\nHello").unwrap().unwrap();
+        assert_eq!(block.front_matter, json!({}));
+        assert_eq!(strip_placeholder(block.body, block.lines), "Hello");
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        let block = split("---\r\ntitle: Intro\r\n---\r\nHello\r\n").unwrap().unwrap();
+        assert_eq!(block.front_matter, json!({ "title": "Intro" }));
+        assert_eq!(block.lines, 3);
+        assert_eq!(strip_placeholder(block.body, block.lines), "Hello\r\n");
+    }
+
+    #[test]
+    fn leaves_chapters_without_a_mapping_block_alone() {
+        assert!(split("# Title\n---\na: 1\n---\n").unwrap().is_none());
+        // Never closed.
+        assert!(split("---\na: 1\nHello\n").unwrap().is_none());
+        // A thematic break followed by prose.
+        assert!(split("---\nJust some text\n---\n").unwrap().is_none());
+        assert!(split("---\n- a\n- b\n---\n").unwrap().is_none());
+    }
+
+    #[test]
+    fn reports_yaml_errors_at_the_chapter_line() {
+        let err = split("---\ntitle: Intro\nnote: this: breaks\n---\n").map(|_| ()).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("parsing front matter: "), "{:#}", err);
+        let line = err.downcast_ref::<crate::format::ParseError>().unwrap().line;
+        assert_eq!(line, Some(3));
+    }
+
+    #[test]
+    fn strips_only_the_placeholder_lines() {
+        assert_eq!(strip_placeholder("\n\n\nText".to_string(), 2), "\nText");
+        // Lines the template itself removed are not taken from the chapter.
+        assert_eq!(strip_placeholder("\nText".to_string(), 3), "Text");
+        assert_eq!(strip_placeholder("Text".to_string(), 3), "Text");
+    }
+}
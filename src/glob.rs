@@ -0,0 +1,68 @@
+use mdbook::errors::Error;
+use regex::Regex;
+
+/// Compiles a glob pattern into an anchored regex.
+///
+/// `*` and `?` never match `sep`, while `**` matches any number of segments, so with `sep = '.'` the
+/// pattern `operators.*.url` matches `operators.mainnet.url` but not `operators.a.b.url`.
+pub fn to_regex(pattern: &str, sep: char) -> Result<Regex, Error> {
+    let sep_class = regex::escape(&sep.to_string());
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&sep) {
+                    // `**/` also matches zero segments.
+                    chars.next();
+                    re.push_str(&format!("(?:.*{})?", sep_class));
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str(&format!("[^{}]*", sep_class)),
+            '?' => re.push_str(&format!("[^{}]", sep_class)),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+
+    Regex::new(&re).map_err(|e| Error::msg(format!("invalid glob pattern {:?}: {}", pattern, e)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, sep: char, text: &str) -> bool {
+        to_regex(pattern, sep).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn single_stars_stay_within_a_segment() {
+        assert!(matches("*.json", '/', "a.json"));
+        assert!(!matches("*.json", '/', "eu/a.json"));
+        assert!(matches("operators.*.url", '.', "operators.mainnet.url"));
+        assert!(!matches("operators.*.url", '.', "operators.a.b.url"));
+        assert!(matches("v?.json", '/', "v1.json"));
+        assert!(!matches("v?.json", '/', "v/.json"));
+    }
+
+    #[test]
+    fn double_stars_cross_segments() {
+        assert!(matches("**/*.json", '/', "a.json"));
+        assert!(matches("**/*.json", '/', "eu/west/a.json"));
+        assert!(matches("eu/**/a.json", '/', "eu/a.json"));
+        assert!(matches("page.**", '.', "page.a.b"));
+        assert!(!matches("eu/**/a.json", '/', "us/a.json"));
+    }
+
+    #[test]
+    fn other_characters_are_literal() {
+        assert!(matches("a+b (1).json", '/', "a+b (1).json"));
+        assert!(!matches("a.json", '/', "abjson"));
+    }
+}
@@ -0,0 +1,228 @@
+use super::{compare, fail, lookup, param, text_param, to_text};
+use handlebars::{Handlebars, Helper, RenderError};
+use regex::Regex;
+use serde_json::Value as Json;
+
+/// `(sort-by list "field")` sorts by a dotted field, descending when it is prefixed with `-`.
+pub fn sort_by(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let mut list = items(h, r)?;
+    let field = text_param(h, r, 1)?;
+    let (field, descending) = match field.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (field.as_str(), false),
+    };
+    list.sort_by(|a, b| {
+        let order = compare(lookup(a, field), lookup(b, field));
+        if descending { order.reverse() } else { order }
+    });
+    Ok(to_array(list))
+}
+
+/// `(filter-by list "field" value)` keeps the items whose field equals `value`. With `op="contains"`
+/// the field must contain it (as a substring or an array item), with `op="matches"` it must match
+/// `value` as a regex.
+pub fn filter_by(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let list = items(h, r)?;
+    let field = text_param(h, r, 1)?;
+    let value = param(h, r, 2)?;
+    let op = match h.hash_get("op").map(|p| p.value()) {
+        None => "equals",
+        Some(Json::String(op)) => op.as_str(),
+        Some(_) => return Err(fail(h, "expects `op` to be a string".to_string())),
+    };
+
+    let keep: Box<dyn Fn(&Json) -> bool> = match op {
+        "equals" => Box::new(|v| same(v, value)),
+        "contains" => Box::new(|v| match v {
+            Json::Array(items) => items.iter().any(|item| same(item, value)),
+            Json::Object(_) => false,
+            v => to_text(v).contains(&to_text(value)),
+        }),
+        "matches" => {
+            let re = Regex::new(&to_text(value)).map_err(|e| fail(h, format!("has an invalid regex: {}", e)))?;
+            Box::new(move |v| !v.is_array() && !v.is_object() && re.is_match(&to_text(v)))
+        }
+        other => return Err(fail(h, format!("has unknown op `{}`; use equals, contains or matches", other))),
+    };
+    Ok(to_array(list.into_iter().filter(|item| lookup(item, &field).is_some_and(&keep)).collect()))
+}
+
+/// `(where list network="mainnet" region="eu")` keeps the items whose fields equal every hash value.
+pub fn where_(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let list = items(h, r)?;
+    let conditions: Vec<(&str, &Json)> = h.hash().iter().map(|(k, v)| (*k, v.value())).collect();
+    Ok(to_array(
+        list.into_iter()
+            .filter(|item| conditions.iter().all(|(k, v)| lookup(item, k).is_some_and(|found| same(found, v))))
+            .collect(),
+    ))
+}
+
+/// `(group-by list "field")` gives an object mapping each value of the field to the items having it,
+/// in their original order. Items without the field are left out.
+pub fn group_by(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let list = items(h, r)?;
+    let field = text_param(h, r, 1)?;
+    let mut groups = serde_json::Map::new();
+    for item in list {
+        let Some(key) = lookup(item, &field) else {
+            continue;
+        };
+        let group = groups.entry(to_text(key)).or_insert_with(|| Json::Array(Vec::new()));
+        group.as_array_mut().unwrap().push(item.clone());
+    }
+    Ok(Json::Object(groups))
+}
+
+/// `(unique list)` drops repeated items; `(unique list "field")` keeps the first item for each value
+/// of the field.
+pub fn unique(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let list = items(h, r)?;
+    let field = match h.param(1) {
+        Some(_) => Some(text_param(h, r, 1)?),
+        None => None,
+    };
+    let mut seen: Vec<Option<&Json>> = Vec::new();
+    let mut kept = Vec::new();
+    for item in list {
+        let key = match &field {
+            Some(field) => lookup(item, field),
+            None => Some(item),
+        };
+        if !seen.contains(&key) {
+            seen.push(key);
+            kept.push(item);
+        }
+    }
+    Ok(to_array(kept))
+}
+
+/// `(pluck list "field")` gives the field of every item that has it.
+pub fn pluck(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let list = items(h, r)?;
+    let field = text_param(h, r, 1)?;
+    Ok(to_array(list.into_iter().filter_map(|item| lookup(item, &field)).collect()))
+}
+
+/// `(first list)` gives the first item, or null for an empty list.
+pub fn first(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(items(h, r)?.first().map_or(Json::Null, |item| (*item).clone()))
+}
+
+/// `(last list)` gives the last item, or null for an empty list.
+pub fn last(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(items(h, r)?.last().map_or(Json::Null, |item| (*item).clone()))
+}
+
+/// `(take list 3)` gives the first items of the list, up to the count.
+pub fn take(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let list = items(h, r)?;
+    let count = param(h, r, 1)?
+        .as_u64()
+        .ok_or_else(|| fail(h, "expects a non-negative count at position 2".to_string()))?;
+    Ok(to_array(list.into_iter().take(count as usize).collect()))
+}
+
+/// The list in the first parameter: an array, the values of an object, or nothing for null.
+fn items<'a>(h: &'a Helper, r: &Handlebars) -> Result<Vec<&'a Json>, RenderError> {
+    match param(h, r, 0)? {
+        Json::Array(items) => Ok(items.iter().collect()),
+        Json::Object(map) => Ok(map.values().collect()),
+        Json::Null => Ok(Vec::new()),
+        _ => Err(fail(h, "expects an array or object at position 1".to_string())),
+    }
+}
+
+fn to_array(items: Vec<&Json>) -> Json {
+    Json::Array(items.into_iter().cloned().collect())
+}
+
+/// Equality that lets a scalar match its text, so `"1"` written in a template matches a number `1`.
+fn same(a: &Json, b: &Json) -> bool {
+    a == b || (!a.is_array() && !a.is_object() && !b.is_array() && !b.is_object() && to_text(a) == to_text(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::render;
+    use serde_json::{json, Value as Json};
+
+    fn operators() -> Json {
+        json!({
+            "operators": [
+                { "name": "b", "stake": 20, "network": "mainnet", "region": "eu", "tags": ["rpc", "archive"] },
+                { "name": "a", "stake": 5, "network": "testnet", "region": "us", "tags": ["rpc"] },
+                { "name": "c", "stake": 20, "network": "mainnet", "region": "us" },
+                { "name": "d", "network": "mainnet", "region": "eu" },
+            ]
+        })
+    }
+
+    /// The names of the operators `list` gives, a subexpression over `operators`.
+    fn names(list: &str) -> String {
+        render(&format!("{{{{#each {}}}}}{{{{name}}}}{{{{/each}}}}", list), &operators()).unwrap()
+    }
+
+    #[test]
+    fn sorts_keeping_ties_in_order() {
+        assert_eq!(names("(sort-by operators \"name\")"), "abcd");
+        assert_eq!(names("(sort-by operators \"stake\")"), "dabc");
+        assert_eq!(names("(sort-by operators \"-stake\")"), "bcad");
+        assert_eq!(names("(sort-by operators \"-name\")"), "dcba");
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(names("(filter-by operators \"network\" \"mainnet\")"), "bcd");
+        assert_eq!(names("(filter-by operators \"stake\" \"20\")"), "bc");
+        assert_eq!(names("(filter-by operators \"tags\" \"archive\" op=\"contains\")"), "b");
+        assert_eq!(names("(filter-by operators \"network\" \"net\" op=\"contains\")"), "bacd");
+        assert_eq!(names("(filter-by operators \"name\" \"^[ac]$\" op=\"matches\")"), "ac");
+        // Arrays never match a regex.
+        assert_eq!(names("(filter-by operators \"tags\" \"rpc\" op=\"matches\")"), "");
+
+        let err = render("{{filter-by operators \"name\" \"(\" op=\"matches\"}}", &operators()).unwrap_err();
+        assert!(err.to_string().contains("`filter-by` has an invalid regex"), "{}", err);
+        let err = render("{{filter-by operators \"name\" \"a\" op=\"like\"}}", &operators()).unwrap_err();
+        assert!(err.to_string().contains("`filter-by` has unknown op `like`"), "{}", err);
+    }
+
+    #[test]
+    fn where_matches_every_hash_value() {
+        assert_eq!(names("(where operators network=\"mainnet\" region=\"eu\")"), "bd");
+        assert_eq!(names("(where operators stake=20)"), "bc");
+        assert_eq!(names("(where operators)"), "bacd");
+    }
+
+    #[test]
+    fn groups_by_a_field() {
+        let rendered = render(
+            "{{#each (group-by operators \"stake\")}}{{@key}}:{{#each this}}{{name}}{{/each}} {{/each}}",
+            &operators(),
+        );
+        assert_eq!(rendered.unwrap(), "20:bc 5:a ");
+    }
+
+    #[test]
+    fn unique_items_and_fields() {
+        let data = json!({ "regions": ["eu", "us", "eu", 1, "1"] });
+        assert_eq!(render("{{join (unique regions)}}", &data).unwrap(), "eu, us, 1, 1");
+        assert_eq!(names("(unique operators \"region\")"), "ba");
+        // Items without the field count as one more value.
+        assert_eq!(names("(unique operators \"stake\")"), "bad");
+    }
+
+    #[test]
+    fn picks_items() {
+        assert_eq!(names("(take operators 2)"), "ba");
+        assert_eq!(names("(take operators 9)"), "bacd");
+        assert_eq!(names("(take operators 0)"), "");
+        assert!(render("{{take operators -1}}", &operators()).is_err());
+        let data = operators();
+        assert_eq!(render("{{join (pluck operators \"stake\")}}", &data).unwrap(), "20, 5, 20");
+        let ends = render("{{lookup (first operators) \"name\"}}{{lookup (last operators) \"name\"}}", &data);
+        assert_eq!(ends.unwrap(), "bd");
+        assert_eq!(render("{{first missing}}|{{join (take missing 2)}}", &data).unwrap(), "|");
+        assert!(render("{{first 3}}", &data).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use super::{fail, param, text_param};
+use handlebars::{Handlebars, Helper, RenderError};
+use serde_json::Value as Json;
+
+/// Largest magnitude below which every integer is exactly representable as an `f64`.
+const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+pub fn add(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    arithmetic(h, r, |a, b| a + b)
+}
+
+pub fn sub(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    arithmetic(h, r, |a, b| a - b)
+}
+
+pub fn mul(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    arithmetic(h, r, |a, b| a * b)
+}
+
+pub fn div(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    if number(h, r, 1)? == 0.0 {
+        return Err(fail(h, "cannot divide by zero".to_string()));
+    }
+    arithmetic(h, r, |a, b| a / b)
+}
+
+/// Formats a number with `separator` (default `,`) between groups of thousands and, if given,
+/// exactly `decimals` digits after the point. Without `decimals`, integers are formatted exactly,
+/// however large.
+pub fn format_number(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let n = number(h, r, 0)?;
+    let text = match h.hash_get("decimals").map(|d| d.value()) {
+        Some(Json::Number(d)) if d.is_u64() => format!("{:.*}", d.as_u64().unwrap() as usize, n),
+        Some(_) => return Err(fail(h, "expects `decimals` to be a non-negative integer".to_string())),
+        None => match integer(h, r, 0)? {
+            Some(int) => int.to_string(),
+            // Unlike a JSON number, `f64`'s `Display` never switches to exponent notation.
+            None => n.to_string(),
+        },
+    };
+    let separator = match h.hash_get("separator") {
+        Some(s) => s.value().as_str().ok_or_else(|| fail(h, "expects `separator` to be a string".to_string()))?,
+        None => ",",
+    };
+
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text.as_str()),
+    };
+    let (int, frac) = unsigned.split_at(unsigned.find('.').unwrap_or(unsigned.len()));
+    let mut grouped = String::new();
+    for (i, digit) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    Ok(Json::String(format!("{}{}{}", sign, grouped, frac)))
+}
+
+fn arithmetic(h: &Helper, r: &Handlebars, op: fn(f64, f64) -> f64) -> Result<Json, RenderError> {
+    let result = op(number(h, r, 0)?, number(h, r, 1)?);
+    if !result.is_finite() {
+        return Err(fail(h, "result is out of range".to_string()));
+    }
+    Ok(to_number(result))
+}
+
+/// The parameter at `idx` as a number; numeric strings are parsed.
+fn number(h: &Helper, r: &Handlebars, idx: usize) -> Result<f64, RenderError> {
+    let parsed = match param(h, r, idx)? {
+        Json::Number(n) => n.as_f64(),
+        Json::String(_) => text_param(h, r, idx)?.trim().parse().ok(),
+        _ => None,
+    };
+    parsed.ok_or_else(|| fail(h, format!("expects a number at position {}", idx + 1)))
+}
+
+/// The parameter at `idx` if it is an integer, or a string holding one.
+fn integer(h: &Helper, r: &Handlebars, idx: usize) -> Result<Option<i128>, RenderError> {
+    Ok(match param(h, r, idx)? {
+        Json::Number(n) => n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from)),
+        Json::String(_) => text_param(h, r, idx)?.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+/// Whole results come out as integers, so `{{div 10 2}}` renders `5` rather than `5.0`.
+fn to_number(n: f64) -> Json {
+    if n.fract() == 0.0 && n.abs() < MAX_EXACT {
+        Json::from(n as i64)
+    } else {
+        Json::from(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::render;
+    use serde_json::json;
+
+    fn format(template: &str, data: serde_json::Value) -> String {
+        render(template, &data).unwrap()
+    }
+
+    #[test]
+    fn formats_integers_exactly() {
+        assert_eq!(format("{{format-number n}}", json!({ "n": 1234567 })), "1,234,567");
+        assert_eq!(format("{{format-number n}}", json!({ "n": u64::MAX })), "18,446,744,073,709,551,615");
+        assert_eq!(format("{{format-number n}}", json!({ "n": "98765432109876543210" })), "98,765,432,109,876,543,210");
+        assert_eq!(format("{{format-number n}}", json!({ "n": -1234 })), "-1,234");
+        assert_eq!(format("{{format-number n}}", json!({ "n": 999 })), "999");
+        assert_eq!(format("{{format-number n}}", json!({ "n": 1e20 })), "100,000,000,000,000,000,000");
+    }
+
+    #[test]
+    fn formats_decimals_and_separators() {
+        assert_eq!(format("{{format-number 1234.5 decimals=2}}", json!({})), "1,234.50");
+        assert_eq!(format("{{format-number -1234.567 decimals=1}}", json!({})), "-1,234.6");
+        assert_eq!(format("{{format-number 1234 decimals=0 separator=\" \"}}", json!({})), "1 234");
+        assert_eq!(format("{{format-number -0.5}}", json!({})), "-0.5");
+        assert!(render("{{format-number 1 decimals=-1}}", &json!({})).is_err());
+        assert!(render("{{format-number \"x\"}}", &json!({})).is_err());
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            format("{{add 1 \"2\"}} {{sub 1 3}} {{mul 2 2.5}} {{div 10 4}} {{div 10 2}}", json!({})),
+            "3 -2 5 2.5 5"
+        );
+        assert!(render("{{div 1 0}}", &json!({})).is_err());
+    }
+}
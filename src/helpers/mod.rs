@@ -0,0 +1,221 @@
+mod collections;
+mod math;
+mod query;
+mod table;
+mod text;
+
+use handlebars::{Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason, ScopedJson};
+use serde_json::Value as Json;
+use std::cmp::Ordering;
+
+/// Registers the helper library enabled by `helpers = true`.
+///
+/// Handlebars itself provides `eq`, `ne`, `gt`, `gte`, `lt`, `lte`, `and`, `or`, `not` and `len`.
+/// On top of those:
+///
+/// - `upper`, `lower`, `trim`: `{{upper name}}`
+/// - `kebab`, `snake`: `{{kebab "Sui Wallet"}}` gives `sui-wallet`
+/// - `title`: capitalises each word, `{{title "connect to rpc"}}` gives `Connect To Rpc`
+/// - `replace`: `{{replace url "http:" "https:"}}`
+/// - `join`: `{{join regions ", "}}`, the separator defaulting to `, `
+/// - `default`: `{{default page.subtitle "None"}}` uses the fallback for null, missing or `""`
+/// - `add`, `sub`, `mul`, `div`: `{{add count 1}}`; numeric strings are accepted
+/// - `format-number`: `{{format-number 1234.5 decimals=2 separator=","}}` gives `1,234.50`
+/// - `sort-by`, `filter-by`, `where`, `group-by`, `unique`, `pluck`, `first`, `last`, `take`: list
+///   operations for use as subexpressions, e.g.
+///   `{{#each (sort-by (filter-by operators "network" "mainnet") "name")}}`; see [`collections`]
+/// - `query`: `{{query "/portals/sui-wallet.v2/url"}}` looks up an RFC 6901 JSON pointer, for keys
+///   that Handlebars paths cannot express
+/// - `jsonpath`: `{{#each (jsonpath "$.operators[?(@.region=='eu')].url")}}` gives the array of
+///   values matched by a JSONPath expression; see [`query::jsonpath`] for the supported syntax
+/// - `table`: renders an array of objects as a Markdown table, see [`table::Table`]
+///
+/// All but `table` can share a name with a variable: called without parameters, e.g. `{{title}}`
+/// with a front-matter `title`, they give the variable's value when it is defined.
+pub fn register(hbs: &mut Handlebars) {
+    for (name, f) in VALUE_HELPERS {
+        hbs.register_helper(name, Box::new(ValueHelper(f)));
+    }
+    hbs.register_helper("query", Box::new(RootHelper(query::query)));
+    hbs.register_helper("jsonpath", Box::new(RootHelper(query::jsonpath)));
+    hbs.register_helper("table", Box::new(table::Table));
+}
+
+/// Names of the helpers added by [`register`].
+pub fn names() -> impl Iterator<Item = &'static str> {
+    VALUE_HELPERS
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(["query", "jsonpath", "table"])
+}
+
+/// Whether the helper `name` gives the variable of the same name when called without parameters.
+pub fn reads_own_name(name: &str) -> bool {
+    name != "table" && names().any(|n| n == name)
+}
+
+const VALUE_HELPERS: [(&str, HelperFn); 23] = [
+    ("upper", text::upper),
+    ("lower", text::lower),
+    ("trim", text::trim),
+    ("kebab", text::kebab),
+    ("snake", text::snake),
+    ("title", text::title),
+    ("replace", text::replace),
+    ("join", text::join),
+    ("default", text::default),
+    ("add", math::add),
+    ("sub", math::sub),
+    ("mul", math::mul),
+    ("div", math::div),
+    ("format-number", math::format_number),
+    ("sort-by", collections::sort_by),
+    ("filter-by", collections::filter_by),
+    ("where", collections::where_),
+    ("group-by", collections::group_by),
+    ("unique", collections::unique),
+    ("pluck", collections::pluck),
+    ("first", collections::first),
+    ("last", collections::last),
+    ("take", collections::take),
+];
+
+/// A helper computing a value from its parameters, usable both inline and as a subexpression.
+type HelperFn = fn(&Helper, &Handlebars) -> Result<Json, RenderError>;
+
+struct ValueHelper(HelperFn);
+
+impl HelperDef for ValueHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        if let Some(value) = own_value(h, ctx, rc) {
+            return Ok(value);
+        }
+        (self.0)(h, r).map(ScopedJson::Derived)
+    }
+}
+
+/// A helper computing a value from its parameters and the root of the render context.
+type RootHelperFn = fn(&Helper, &Handlebars, &Json) -> Result<Json, RenderError>;
+
+struct RootHelper(RootHelperFn);
+
+impl HelperDef for RootHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        if let Some(value) = own_value(h, ctx, rc) {
+            return Ok(value);
+        }
+        (self.0)(h, r, ctx.data()).map(ScopedJson::Derived)
+    }
+}
+
+/// For a helper called without parameters, the variable named like it, if defined.
+fn own_value<'rc>(h: &Helper<'rc>, ctx: &'rc Context, rc: &RenderContext<'_, 'rc>) -> Option<ScopedJson<'rc>> {
+    if !h.params().is_empty() || !h.hash().is_empty() {
+        return None;
+    }
+    rc.evaluate(ctx, h.name()).ok().filter(|value| !value.is_missing())
+}
+
+/// The parameter at `idx`. In strict mode a parameter naming a missing variable is reported as such.
+fn param<'a>(h: &'a Helper, r: &Handlebars, idx: usize) -> Result<&'a Json, RenderError> {
+    let Some(p) = h.param(idx) else {
+        return Err(fail(h, format!("expects a parameter at position {}", idx + 1)));
+    };
+    if r.strict_mode() && p.is_value_missing() {
+        return Err(RenderErrorReason::MissingVariable(p.relative_path().cloned()).into());
+    }
+    Ok(p.value())
+}
+
+/// The parameter at `idx` as text. Numbers and booleans are converted; null reads as empty.
+fn text_param(h: &Helper, r: &Handlebars, idx: usize) -> Result<String, RenderError> {
+    match param(h, r, idx)? {
+        Json::Array(_) | Json::Object(_) => Err(fail(h, format!("expects text at position {}", idx + 1))),
+        value => Ok(to_text(value)),
+    }
+}
+
+/// The hash parameter `name`, which must be a string if given.
+fn hash_text(h: &Helper, name: &str) -> Result<Option<String>, RenderError> {
+    match h.hash_get(name).map(|p| p.value()) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(fail(h, format!("expects `{}` to be a string", name))),
+    }
+}
+
+/// An error naming the helper that raised it.
+fn fail(h: &Helper, message: String) -> RenderError {
+    RenderErrorReason::Other(format!("`{}` {}", h.name(), message)).into()
+}
+
+/// How a value reads when written into a chapter: strings as-is, null as nothing, the rest as JSON.
+fn to_text(value: &Json) -> String {
+    match value {
+        Json::String(s) => s.clone(),
+        Json::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// The value at the dotted `path` below `value`, e.g. `links.docs` or `regions.0`.
+fn lookup<'a>(value: &'a Json, path: &str) -> Option<&'a Json> {
+    path.split('.').try_fold(value, |v, segment| match v {
+        Json::Object(map) => map.get(segment),
+        Json::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Orders numbers numerically and everything else by its text, missing values first.
+fn compare(a: Option<&Json>, b: Option<&Json>) -> Ordering {
+    match (a, b) {
+        (Some(Json::Number(a)), Some(Json::Number(b))) => {
+            a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal)
+        }
+        (a, b) => a.map(to_text).cmp(&b.map(to_text)),
+    }
+}
+
+/// Renders `template` against `data` with the helper library registered.
+#[cfg(test)]
+fn render(template: &str, data: &Json) -> Result<String, RenderError> {
+    let mut hbs = Handlebars::new();
+    hbs.register_escape_fn(handlebars::no_escape);
+    register(&mut hbs);
+    hbs.render_template(template, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bare_helpers_read_a_variable_of_the_same_name() {
+        let data = json!({ "title": "Intro", "items": [{ "first": "a" }] });
+        assert_eq!(render("# {{title}} {{title \"x y\"}}", &data).unwrap(), "# Intro X Y");
+        assert_eq!(render("{{#each items}}{{first}}{{/each}}", &data).unwrap(), "a");
+        // Without a variable it is still the helper, asking for its parameter.
+        let err = render("{{upper}}", &data).unwrap_err().to_string();
+        assert!(err.contains("`upper` expects a parameter at position 1"), "{}", err);
+    }
+
+    #[test]
+    fn only_value_helpers_read_their_own_name() {
+        assert!(reads_own_name("title") && reads_own_name("sort-by") && reads_own_name("query"));
+        assert!(!reads_own_name("table") && !reads_own_name("if") && !reads_own_name("nope"));
+    }
+}
@@ -0,0 +1,374 @@
+use super::{compare, fail, param, text_param};
+use handlebars::{Handlebars, Helper, RenderError, RenderErrorReason};
+use regex::Regex;
+use serde_json::Value as Json;
+use std::cmp::Ordering;
+
+/// `{{query "/portals/sui-wallet.v2/url"}}` looks up an RFC 6901 JSON pointer in the render context,
+/// or in the value given as second parameter. In strict mode a pointer that matches nothing is
+/// reported as an undefined variable.
+pub fn query(h: &Helper, r: &Handlebars, root: &Json) -> Result<Json, RenderError> {
+    let pointer = text_param(h, r, 0)?;
+    let target = match h.param(1) {
+        Some(_) => param(h, r, 1)?,
+        None => root,
+    };
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(fail(h, format!("expects a JSON pointer starting with `/`, got `{}`", pointer)));
+    }
+    match target.pointer(&pointer) {
+        Some(value) => Ok(value.clone()),
+        None if r.strict_mode() => Err(RenderErrorReason::MissingVariable(Some(pointer)).into()),
+        None => Ok(Json::Null),
+    }
+}
+
+/// `{{jsonpath "$.operators[?(@.region=='eu')].url"}}` gives the array of values matched by a
+/// JSONPath expression in the render context, or in the value given as second parameter.
+///
+/// Supported: `$`, `.name`, `['name']`, `[0]` and `[-1]`, `[1:3]`, `*`, `..` (any depth), unions
+/// like `['a','b']` or `[0,2]`, and filters `[?(@.path)]` and `[?(@.path OP literal)]` with `==`,
+/// `!=`, `<`, `<=`, `>` or `>=` against a quoted string, number, `true`, `false` or `null`.
+pub fn jsonpath(h: &Helper, r: &Handlebars, root: &Json) -> Result<Json, RenderError> {
+    let path = text_param(h, r, 0)?;
+    let target = match h.param(1) {
+        Some(_) => param(h, r, 1)?,
+        None => root,
+    };
+    let steps = parse(&path).map_err(|e| fail(h, format!("has an invalid JSONPath `{}`: {}", path, e)))?;
+
+    let mut nodes = vec![target];
+    for step in &steps {
+        let candidates: Vec<&Json> = if step.recursive {
+            nodes.into_iter().flat_map(descendants).collect()
+        } else {
+            nodes
+        };
+        nodes = candidates.into_iter().flat_map(|node| step.selector.apply(node)).collect();
+    }
+    Ok(Json::Array(nodes.into_iter().cloned().collect()))
+}
+
+struct Step {
+    /// Applies the selector to the current nodes and all their descendants (`..`).
+    recursive: bool,
+    selector: Selector,
+}
+
+enum Selector {
+    Name(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    Union(Vec<Selector>),
+    Filter(Filter),
+}
+
+struct Filter {
+    path: Vec<String>,
+    /// Comparison operator and literal; `None` tests for existence.
+    comparison: Option<(String, Json)>,
+}
+
+impl Selector {
+    fn apply<'a>(&self, node: &'a Json) -> Vec<&'a Json> {
+        match (self, node) {
+            (Selector::Name(name), Json::Object(map)) => map.get(name).into_iter().collect(),
+            (Selector::Index(idx), Json::Array(items)) => resolve_index(*idx, items.len())
+                .and_then(|i| items.get(i))
+                .into_iter()
+                .collect(),
+            (Selector::Slice(start, end), Json::Array(items)) => {
+                let len = items.len() as i64;
+                let clamp = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) } as usize;
+                let start = clamp(start.unwrap_or(0));
+                let end = clamp(end.unwrap_or(len));
+                items.get(start..end.max(start)).unwrap_or_default().iter().collect()
+            }
+            (Selector::Wildcard, node) => children(node),
+            (Selector::Union(selectors), node) => selectors.iter().flat_map(|s| s.apply(node)).collect(),
+            (Selector::Filter(filter), node) => children(node).into_iter().filter(|c| filter.matches(c)).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, node: &Json) -> bool {
+        let found = self.path.iter().try_fold(node, |v, segment| match v {
+            Json::Object(map) => map.get(segment),
+            Json::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        });
+        let Some((op, literal)) = &self.comparison else {
+            return found.is_some();
+        };
+        let Some(found) = found else {
+            return false;
+        };
+        let ordering = compare(Some(found), Some(literal));
+        let comparable = found.is_number() == literal.is_number();
+        match op.as_str() {
+            "==" => found == literal,
+            "!=" => found != literal,
+            "<" => comparable && ordering == Ordering::Less,
+            "<=" => comparable && ordering != Ordering::Greater,
+            ">" => comparable && ordering == Ordering::Greater,
+            ">=" => comparable && ordering != Ordering::Less,
+            _ => false,
+        }
+    }
+}
+
+fn children(node: &Json) -> Vec<&Json> {
+    match node {
+        Json::Array(items) => items.iter().collect(),
+        Json::Object(map) => map.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `node` followed by everything below it, depth first.
+fn descendants(node: &Json) -> Vec<&Json> {
+    let mut out = vec![node];
+    for child in children(node) {
+        out.extend(descendants(child));
+    }
+    out
+}
+
+fn resolve_index(idx: i64, len: usize) -> Option<usize> {
+    if idx < 0 {
+        len.checked_sub(idx.unsigned_abs() as usize)
+    } else {
+        Some(idx as usize)
+    }
+}
+
+fn parse(path: &str) -> Result<Vec<Step>, String> {
+    let mut rest = path.trim().strip_prefix('$').ok_or("must start with `$`")?;
+    let name_re = Regex::new(r"^[^.\[\]\s]+").unwrap();
+    let mut steps = Vec::new();
+
+    while !rest.is_empty() {
+        let recursive = rest.starts_with("..");
+        if recursive {
+            rest = &rest[2..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+        } else if !rest.starts_with('[') {
+            return Err(format!("unexpected `{}`", rest));
+        }
+
+        let selector = if let Some(after) = rest.strip_prefix('[') {
+            let end = bracket_end(after).ok_or("unclosed `[`")?;
+            rest = &after[end + 1..];
+            parse_bracket(after[..end].trim())?
+        } else if let Some(after) = rest.strip_prefix('*') {
+            rest = after;
+            Selector::Wildcard
+        } else {
+            let name = name_re.find(rest).ok_or("expected a name after `.`")?.as_str();
+            rest = &rest[name.len()..];
+            Selector::Name(name.to_string())
+        };
+        steps.push(Step { recursive, selector });
+    }
+    Ok(steps)
+}
+
+/// Position of the `]` closing a bracket whose content starts `text`, skipping quoted strings and
+/// parenthesised filters.
+fn bracket_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ']') if depth == 0 => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_bracket(content: &str) -> Result<Selector, String> {
+    if content == "*" {
+        return Ok(Selector::Wildcard);
+    }
+    if let Some(filter) = content.strip_prefix('?') {
+        return parse_filter(filter.trim()).map(Selector::Filter);
+    }
+
+    // Members are told apart before looking for `:`, which may appear inside a quoted name.
+    let mut selectors = split_union(content)
+        .into_iter()
+        .map(|item| {
+            if let Some(name) = unquote(item) {
+                Ok(Selector::Name(name))
+            } else if let Some((start, end)) = item.split_once(':') {
+                Ok(Selector::Slice(slice_bound(start)?, slice_bound(end)?))
+            } else {
+                item.parse().map(Selector::Index).map_err(|_| format!("invalid selector `[{}]`", item))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(if selectors.len() == 1 {
+        selectors.pop().unwrap()
+    } else {
+        Selector::Union(selectors)
+    })
+}
+
+fn slice_bound(bound: &str) -> Result<Option<i64>, String> {
+    let bound = bound.trim();
+    if bound.is_empty() {
+        Ok(None)
+    } else {
+        bound.parse().map(Some).map_err(|_| format!("invalid slice bound `{}`", bound))
+    }
+}
+
+fn parse_filter(filter: &str) -> Result<Filter, String> {
+    let re = Regex::new(r"^\(\s*@((?:\.[^.\s=!<>()]+)*)\s*(?:(==|!=|<=|>=|<|>)\s*(.+?))?\s*\)$").unwrap();
+    let caps = re.captures(filter).ok_or_else(|| format!("unsupported filter `?{}`", filter))?;
+    let path = caps[1].split('.').filter(|s| !s.is_empty()).map(str::to_string).collect();
+    let comparison = match (caps.get(2), caps.get(3)) {
+        (Some(op), Some(literal)) => Some((op.as_str().to_string(), parse_literal(literal.as_str())?)),
+        _ => None,
+    };
+    Ok(Filter { path, comparison })
+}
+
+fn parse_literal(literal: &str) -> Result<Json, String> {
+    if let Some(text) = unquote(literal) {
+        return Ok(Json::String(text));
+    }
+    serde_json::from_str(literal).map_err(|_| format!("invalid literal `{}`", literal))
+}
+
+/// The content of a single- or double-quoted string.
+fn unquote(text: &str) -> Option<String> {
+    let quote = text.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    text.strip_prefix(quote)?.strip_suffix(quote).map(str::to_string)
+}
+
+/// Splits union members at commas outside quotes.
+fn split_union(content: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in content.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ',') => {
+                items.push(content[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(content[start..].trim());
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use handlebars::{handlebars_helper, Handlebars};
+    use serde_json::{json, Value as Json};
+
+    handlebars_helper!(to_json: |v: Json| v.to_string());
+
+    fn data() -> Json {
+        json!({
+            "portals": { "sui-wallet.v2": { "url": "https://wallet" }, "a:b": 1, "a/b": 2 },
+            "operators": [
+                { "name": "alpha", "region": "eu", "stake": 10, "url": "https://a" },
+                { "name": "beta", "region": "us", "stake": 30 },
+                { "name": "gamma", "region": "eu", "stake": 20, "url": "https://c" }
+            ]
+        })
+    }
+
+    fn render(template: &str) -> Result<String, String> {
+        let mut hbs = Handlebars::new();
+        super::super::register(&mut hbs);
+        hbs.register_helper("json", Box::new(to_json));
+        hbs.render_template(template, &data()).map_err(|e| e.to_string())
+    }
+
+    fn jsonpath(path: &str) -> Json {
+        let rendered = render(&format!("{{{{{{json (jsonpath \"{}\")}}}}}}", path)).unwrap();
+        serde_json::from_str(&rendered).unwrap()
+    }
+
+    #[test]
+    fn query_follows_json_pointers() {
+        assert_eq!(render(r#"{{query "/portals/sui-wallet.v2/url"}}"#).unwrap(), "https://wallet");
+        assert_eq!(render(r#"{{query "/portals/a~1b"}}"#).unwrap(), "2");
+        assert_eq!(render(r#"{{query "/url" portals.[sui-wallet.v2]}}"#).unwrap(), "https://wallet");
+        assert_eq!(render(r#"{{query "/missing"}}"#).unwrap(), "");
+        assert!(render(r#"{{query "portals"}}"#).unwrap_err().contains("JSON pointer"));
+    }
+
+    #[test]
+    fn query_reports_missing_pointers_in_strict_mode() {
+        let mut hbs = Handlebars::new();
+        super::super::register(&mut hbs);
+        hbs.set_strict_mode(true);
+        let err = hbs.render_template(r#"{{query "/nope"}}"#, &data()).unwrap_err();
+        assert!(err.to_string().contains("/nope"), "{}", err);
+    }
+
+    #[test]
+    fn names_indices_and_wildcards() {
+        assert_eq!(jsonpath("$.operators[0].name"), json!(["alpha"]));
+        assert_eq!(jsonpath("$.operators[-1].name"), json!(["gamma"]));
+        assert_eq!(jsonpath("$['portals']['sui-wallet.v2'].url"), json!(["https://wallet"]));
+        assert_eq!(jsonpath("$.operators[*].url"), json!(["https://a", "https://c"]));
+        assert_eq!(jsonpath("$.operators.*.stake"), json!([10, 30, 20]));
+        assert_eq!(jsonpath("$.operators[5]"), json!([]));
+    }
+
+    #[test]
+    fn slices_and_unions() {
+        assert_eq!(jsonpath("$.operators[1:].name"), json!(["beta", "gamma"]));
+        assert_eq!(jsonpath("$.operators[:-1].name"), json!(["alpha", "beta"]));
+        assert_eq!(jsonpath("$.operators[0,2].name"), json!(["alpha", "gamma"]));
+        assert_eq!(jsonpath("$.operators[0]['name','region']"), json!(["alpha", "eu"]));
+    }
+
+    #[test]
+    fn quoted_names_may_contain_colons() {
+        assert_eq!(jsonpath("$.portals['a:b']"), json!([1]));
+        assert_eq!(jsonpath("$.portals['a:b','a/b']"), json!([1, 2]));
+    }
+
+    #[test]
+    fn recursive_descent() {
+        assert_eq!(jsonpath("$..url"), json!(["https://a", "https://c", "https://wallet"]));
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(jsonpath("$.operators[?(@.region=='eu')].name"), json!(["alpha", "gamma"]));
+        assert_eq!(jsonpath("$.operators[?(@.url)].name"), json!(["alpha", "gamma"]));
+        assert_eq!(jsonpath("$.operators[?(@.stake >= 20)].name"), json!(["beta", "gamma"]));
+        assert_eq!(jsonpath("$.operators[?(@.stake != 30)].name"), json!(["alpha", "gamma"]));
+        assert_eq!(jsonpath("$.operators[?(@.name < 'b')].name"), json!(["alpha"]));
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        for path in ["operators", "$.operators[0", "$.operators[x]", "$.operators[a:b]", "$[?(@.x ~ 1)]"] {
+            let err = render(&format!("{{{{jsonpath \"{}\"}}}}", path)).unwrap_err();
+            assert!(err.contains("invalid JSONPath"), "{}: {}", path, err);
+        }
+    }
+}
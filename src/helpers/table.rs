@@ -0,0 +1,223 @@
+use super::{compare, fail, hash_text, lookup, param, to_text};
+use crate::escape;
+use handlebars::{Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError};
+use regex::{Captures, Regex};
+use serde_json::Value as Json;
+
+/// `{{table operators columns="name,url,region" headers="Name,Endpoint,Region" sort="name"}}`
+/// renders an array (or the values of an object) of objects as a Markdown table.
+///
+/// - `columns`: comma-separated dotted paths into each row; defaults to the keys of the first row
+/// - `headers`: comma-separated header texts, one per column; defaults to the column paths
+/// - `sort`: column to sort rows by, descending when prefixed with `-`
+/// - `align`: `left`, `right` or `center` for every column, or a comma-separated list, one per column
+/// - `format-<column>`: replaces the cell with a template where `{path}` stands for a field of the
+///   row, e.g. `format-region="{region} ({country})"`
+/// - `link-<column>`: turns the cell into a link to a URL template of the same kind, e.g.
+///   `link-name="https://explorer.example/{address}"`
+///
+/// Cell content is escaped for Markdown, including pipes. The table is written as-is, bypassing the
+/// configured `escape` mode.
+pub struct Table;
+
+impl HelperDef for Table {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        out.write(&render(h, r)?)?;
+        Ok(())
+    }
+}
+
+fn render(h: &Helper, r: &Handlebars) -> Result<String, RenderError> {
+    let mut rows: Vec<&Json> = match param(h, r, 0)? {
+        Json::Array(items) => items.iter().collect(),
+        Json::Object(map) => map.values().collect(),
+        Json::Null => Vec::new(),
+        _ => return Err(fail(h, "expects an array of objects".to_string())),
+    };
+
+    let columns = match hash_text(h, "columns")? {
+        Some(columns) => split_list(&columns),
+        None => match rows.first() {
+            Some(Json::Object(first)) => first.keys().cloned().collect(),
+            _ => Vec::new(),
+        },
+    };
+    if columns.is_empty() {
+        return Err(fail(h, "needs `columns` when the first row is not an object".to_string()));
+    }
+    let headers = match hash_text(h, "headers")? {
+        Some(headers) => split_list(&headers),
+        None => columns.clone(),
+    };
+    if headers.len() != columns.len() {
+        return Err(fail(h, format!("has {} headers for {} columns", headers.len(), columns.len())));
+    }
+    let aligns = match hash_text(h, "align")? {
+        Some(align) => split_list(&align),
+        None => vec![String::new()],
+    };
+    if aligns.len() != 1 && aligns.len() != columns.len() {
+        return Err(fail(h, format!("has {} alignments for {} columns", aligns.len(), columns.len())));
+    }
+
+    if let Some(sort) = hash_text(h, "sort")? {
+        let (key, descending) = match sort.strip_prefix('-') {
+            Some(key) => (key.to_string(), true),
+            None => (sort, false),
+        };
+        rows.sort_by(|a, b| {
+            let order = compare(lookup(a, &key), lookup(b, &key));
+            if descending { order.reverse() } else { order }
+        });
+    }
+
+    let mut lines = vec![row_line(headers.iter().map(|header| escape::table_cell(header)))];
+    let separators = (0..columns.len())
+        .map(|i| separator(h, &aligns[if aligns.len() == 1 { 0 } else { i }]))
+        .collect::<Result<Vec<_>, _>>()?;
+    lines.push(row_line(separators.into_iter()));
+    for row in rows {
+        let cells = columns
+            .iter()
+            .map(|column| cell(h, row, column))
+            .collect::<Result<Vec<_>, _>>()?;
+        lines.push(row_line(cells.into_iter()));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn cell(h: &Helper, row: &Json, column: &str) -> Result<String, RenderError> {
+    let mut cell = match hash_text(h, &format!("format-{}", column))? {
+        Some(template) => fill(&template, row, escape::table_cell),
+        None => escape::table_cell(&cell_text(lookup(row, column))),
+    };
+    if let Some(template) = hash_text(h, &format!("link-{}", column))? {
+        let url = fill(&template, row, escape::table_link_destination);
+        if !url.is_empty() {
+            cell = format!("[{}]({})", cell, url);
+        }
+    }
+    Ok(cell)
+}
+
+/// Replaces each `{path}` in `template` with the field of `row` at that path, passed through `esc`.
+fn fill(template: &str, row: &Json, esc: fn(&str) -> String) -> String {
+    let field = Regex::new(r"\{([^{}]+)\}").unwrap();
+    field
+        .replace_all(template, |caps: &Captures| esc(&cell_text(lookup(row, caps[1].trim()))))
+        .into_owned()
+}
+
+/// Text of a cell value; arrays are listed with commas.
+fn cell_text(value: Option<&Json>) -> String {
+    match value {
+        Some(Json::Array(items)) => items.iter().map(to_text).collect::<Vec<_>>().join(", "),
+        Some(value) => to_text(value),
+        None => String::new(),
+    }
+}
+
+fn separator(h: &Helper, align: &str) -> Result<String, RenderError> {
+    match align {
+        "" => Ok("---".to_string()),
+        "left" | "l" => Ok(":---".to_string()),
+        "right" | "r" => Ok("---:".to_string()),
+        "center" | "c" => Ok(":---:".to_string()),
+        other => Err(fail(h, format!("has unknown alignment `{}`", other))),
+    }
+}
+
+fn row_line(cells: impl Iterator<Item = String>) -> String {
+    format!("| {} |", cells.collect::<Vec<_>>().join(" | "))
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(',').map(|item| item.trim().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::render;
+    use serde_json::json;
+
+    fn table(hash: &str) -> Result<String, handlebars::RenderError> {
+        let data = json!({
+            "operators": [
+                { "name": "Mysten", "url": "https://a.example", "stake": 20, "region": "eu", "country": "fr" },
+                { "name": "Pipe|Co", "url": "https://b.example/(b)", "stake": 5, "region": "us", "country": "ca" },
+                { "name": "Zed", "stake": 20, "region": "eu" },
+            ]
+        });
+        render(&format!("{{{{table operators {}}}}}", hash), &data)
+    }
+
+    #[test]
+    fn defaults_to_the_keys_of_the_first_row() {
+        let rendered = render("{{table rows}}", &json!({ "rows": [{ "x": 1, "y": [1, 2] }, { "x": 2 }] }));
+        assert_eq!(rendered.unwrap(), "| x | y |\n| --- | --- |\n| 1 | 1, 2 |\n| 2 |  |");
+        // Objects give their values, in key order.
+        let rendered = render("{{table rows}}", &json!({ "rows": { "b": { "x": 1 }, "a": { "x": 2 } } }));
+        assert_eq!(rendered.unwrap(), "| x |\n| --- |\n| 2 |\n| 1 |");
+        assert_eq!(render("{{table missing columns=\"a\"}}", &json!({})).unwrap(), "| a |\n| --- |");
+    }
+
+    #[test]
+    fn sorts_rows_keeping_ties_in_order() {
+        let names = |hash: &str| {
+            table(&format!("columns=\"name\" {}", hash))
+                .unwrap()
+                .lines()
+                .skip(2)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(names("sort=\"stake\""), "| Pipe\\|Co | | Mysten | | Zed |");
+        assert_eq!(names("sort=\"-stake\""), "| Mysten | | Zed | | Pipe\\|Co |");
+        assert_eq!(names("sort=\"-name\""), "| Zed | | Pipe\\|Co | | Mysten |");
+    }
+
+    #[test]
+    fn aligns_columns() {
+        let separator = |align: &str| {
+            table(&format!("columns=\"name,stake\" align=\"{}\"", align)).map(|t| t.lines().nth(1).unwrap().to_string())
+        };
+        assert_eq!(separator("right").unwrap(), "| ---: | ---: |");
+        assert_eq!(separator("l, center").unwrap(), "| :--- | :---: |");
+        let err = separator("left,right,center").unwrap_err().to_string();
+        assert!(err.contains("`table` has 3 alignments for 2 columns"), "{}", err);
+        assert!(separator("middle").unwrap_err().to_string().contains("`table` has unknown alignment `middle`"));
+    }
+
+    #[test]
+    fn formats_and_links_cells() {
+        let rendered = table(
+            "columns=\"name,region\" headers=\"Name,Region\" format-region=\"{region} ({ country })\" \
+             link-name=\"{url}\"",
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "| Name | Region |\n\
+             | --- | --- |\n\
+             | [Mysten](https://a.example) | eu (fr) |\n\
+             | [Pipe\\|Co](https://b.example/\\(b\\)) | us (ca) |\n\
+             | Zed | eu () |"
+        );
+    }
+
+    #[test]
+    fn headers_must_match_the_columns() {
+        let err = table("columns=\"name,url\" headers=\"Name\"").unwrap_err().to_string();
+        assert!(err.contains("`table` has 1 headers for 2 columns"), "{}", err);
+        let err = render("{{table rows}}", &json!({ "rows": [1, 2] })).unwrap_err().to_string();
+        assert!(err.contains("`table` needs `columns` when the first row is not an object"), "{}", err);
+        assert!(render("{{table 3}}", &json!({})).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use super::{fail, param, text_param, to_text};
+use handlebars::{Handlebars, Helper, RenderError};
+use serde_json::Value as Json;
+
+pub fn upper(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(Json::String(text_param(h, r, 0)?.to_uppercase()))
+}
+
+pub fn lower(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(Json::String(text_param(h, r, 0)?.to_lowercase()))
+}
+
+pub fn trim(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(Json::String(text_param(h, r, 0)?.trim().to_string()))
+}
+
+pub fn kebab(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(Json::String(words(&text_param(h, r, 0)?).join("-")))
+}
+
+pub fn snake(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    Ok(Json::String(words(&text_param(h, r, 0)?).join("_")))
+}
+
+/// Capitalises the first letter of every whitespace-separated word, leaving the rest as written.
+pub fn title(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let text = text_param(h, r, 0)?;
+    let mut out = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if word_start {
+            out.extend(c.to_uppercase());
+        } else {
+            out.push(c);
+        }
+        word_start = c.is_whitespace();
+    }
+    Ok(Json::String(out))
+}
+
+pub fn replace(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let text = text_param(h, r, 0)?;
+    let from = text_param(h, r, 1)?;
+    let to = text_param(h, r, 2)?;
+    if from.is_empty() {
+        return Err(fail(h, "cannot replace an empty string".to_string()));
+    }
+    Ok(Json::String(text.replace(&from, &to)))
+}
+
+/// Joins the items of an array with the separator given as second parameter, `, ` by default.
+pub fn join(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    let items = match param(h, r, 0)? {
+        Json::Array(items) => items,
+        Json::Null => return Ok(Json::String(String::new())),
+        _ => return Err(fail(h, "expects an array".to_string())),
+    };
+    let separator = match h.param(1) {
+        Some(_) => text_param(h, r, 1)?,
+        None => ", ".to_string(),
+    };
+    Ok(Json::String(items.iter().map(to_text).collect::<Vec<_>>().join(&separator)))
+}
+
+/// The first parameter, or the second if the first is null, missing or an empty string.
+pub fn default(h: &Helper, r: &Handlebars) -> Result<Json, RenderError> {
+    // Not `param`: a missing value is exactly what this helper is for, even in strict mode.
+    let value = h.param(0).map(|p| p.value());
+    match value {
+        None | Some(Json::Null) => param(h, r, 1).cloned(),
+        Some(Json::String(s)) if s.is_empty() => param(h, r, 1).cloned(),
+        Some(value) => Ok(value.clone()),
+    }
+}
+
+/// Splits `text` into lowercase words at non-alphanumeric characters and lower-to-upper case changes,
+/// so `SuiWallet v2`, `sui_wallet-v2` and `Sui Wallet V2` all give `sui`, `wallet`, `v2`.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else {
+            if c.is_uppercase() && prev.is_some_and(|p| p.is_lowercase() || p.is_numeric()) && !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            current.extend(c.to_lowercase());
+        }
+        prev = Some(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::render;
+    use serde_json::json;
+
+    #[test]
+    fn splits_words_at_case_changes_and_punctuation() {
+        for text in ["SuiWallet v2", "sui_wallet-v2", "Sui Wallet V2", "  sui..wallet V2 "] {
+            assert_eq!(words(text), ["sui", "wallet", "v2"], "{}", text);
+        }
+        assert_eq!(words("RPCNode"), ["rpcnode"]);
+        assert_eq!(words("v2Beta"), ["v2", "beta"]);
+        assert!(words("--").is_empty());
+    }
+
+    #[test]
+    fn case_helpers() {
+        let data = json!({ "name": "Sui Wallet v2", "n": 3 });
+        assert_eq!(
+            render("{{kebab name}} {{snake name}} {{upper name}} {{lower name}}", &data).unwrap(),
+            "sui-wallet-v2 sui_wallet_v2 SUI WALLET V2 sui wallet v2"
+        );
+        assert_eq!(render("{{title \"connect to  rpc-node\"}} {{title n}}", &data).unwrap(), "Connect To  Rpc-node 3");
+        assert_eq!(render("{{title \"éte iOS\"}}", &data).unwrap(), "Éte IOS");
+    }
+
+    #[test]
+    fn text_helpers() {
+        let data = json!({ "regions": ["eu", 2, null], "empty": "" });
+        let joined = render("{{join regions}}|{{join regions \"/\"}}|{{join missing}}", &data);
+        assert_eq!(joined.unwrap(), "eu, 2, |eu/2/|");
+        assert_eq!(render("{{replace \"http://a\" \"http:\" \"https:\"}}", &data).unwrap(), "https://a");
+        let defaults = render("{{default empty \"none\"}} {{default missing 1}} {{default 0 1}}", &data);
+        assert_eq!(defaults.unwrap(), "none 1 0");
+        assert!(render("{{replace \"a\" \"\" \"b\"}}", &data).is_err());
+        assert!(render("{{upper regions}}", &data).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use crate::data;
+use crate::format::Format;
+use crate::merge::escape_token;
+use crate::render::{self, Renderer};
+use mdbook::errors::Error;
+use regex::Regex;
+use serde_json::{json, Value as Json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: mdbook-template context [book-dir] [--format json|yaml] [--with-provenance] \
+                     [--chapter <path>]";
+
+/// Arguments of `mdbook-template context`.
+struct Args {
+    book_dir: PathBuf,
+    yaml: bool,
+    with_provenance: bool,
+    /// Dump the context of this chapter, with its front matter, `page` and `chapter`, instead of
+    /// the part shared by all chapters.
+    chapter: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, Error> {
+        let mut args = args.into_iter();
+        let mut book_dir = None;
+        let mut yaml = false;
+        let mut with_provenance = false;
+        let mut chapter = None;
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| {
+                args.next()
+                    .ok_or_else(|| Error::msg(format!("`{}` expects a value\n{}", flag, USAGE)))
+            };
+            match arg.as_str() {
+                "--format" => {
+                    yaml = match value("--format")?.as_str() {
+                        "json" => false,
+                        "yaml" => true,
+                        other => return Err(Error::msg(format!("unknown format `{}`\n{}", other, USAGE))),
+                    }
+                }
+                "--with-provenance" => with_provenance = true,
+                "--chapter" => chapter = Some(PathBuf::from(value("--chapter")?)),
+                flag if flag.starts_with("--") => {
+                    return Err(Error::msg(format!("unknown option `{}`\n{}", flag, USAGE)))
+                }
+                _ if book_dir.is_none() => book_dir = Some(PathBuf::from(arg)),
+                _ => return Err(Error::msg(USAGE)),
+            }
+        }
+        Ok(Args {
+            book_dir: book_dir.unwrap_or_else(|| PathBuf::from(".")),
+            yaml,
+            with_provenance,
+            chapter,
+        })
+    }
+}
+
+/// `mdbook-template context [book-dir]`: prints the merged data context with the `book` and
+/// `renderer` variables, or with `--chapter <path>` the full context that chapter is rendered with,
+/// as JSON or with `--format yaml` as YAML.
+///
+/// With `--with-provenance` every leaf value is replaced by `{ value, source }`, where `source` names
+/// the data file that supplied it (`assets/portals.json:12`), the chapter for front matter
+/// (`src/intro.md:3 (front matter)`), or `built-in`. Lines are found by searching the file for the
+/// keys leading to the value, so they are left out when a key cannot be found.
+pub fn run(args: impl IntoIterator<Item = String>) -> Result<(), Error> {
+    let args = Args::parse(args)?;
+    let book = render::load_book(&args.book_dir)?;
+    let renderer = Renderer::new(&book.config, &book.root, "html")?;
+
+    let (context, chapter) = match &args.chapter {
+        Some(wanted) => {
+            let ch = render::find_chapter(&book, wanted)?;
+            let source_path = ch.source_path.as_deref().unwrap_or(wanted);
+            let context = renderer.prepare(ch)?.context;
+            let file = book.config.book.src.join(source_path).display().to_string();
+            (context, Some((file, ch.content.clone())))
+        }
+        None => (renderer.book_context(), None),
+    };
+
+    let output = if args.with_provenance {
+        let mut sources = Sources {
+            renderer: &renderer,
+            data_root: data::data_root(&renderer.cfg, &book.root),
+            page: context.get("page").cloned(),
+            chapter,
+            files: BTreeMap::new(),
+            key_patterns: BTreeMap::new(),
+        };
+        sources.annotate(&context, "")
+    } else {
+        context
+    };
+
+    if args.yaml {
+        print!("{}", serde_norway::to_string(&output).map_err(|e| Error::msg(e.to_string()))?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    }
+    Ok(())
+}
+
+/// Works out where the values of a context came from.
+struct Sources<'a> {
+    renderer: &'a Renderer,
+    data_root: PathBuf,
+    /// The front matter of the chapter being dumped.
+    page: Option<Json>,
+    /// File name and content of the chapter being dumped.
+    chapter: Option<(String, String)>,
+    /// Content and parsed value of the data files read so far, by path as configured; `None` if
+    /// the file could not be read again.
+    files: BTreeMap<String, Option<(String, Json)>>,
+    key_patterns: KeyPatterns,
+}
+
+impl Sources<'_> {
+    /// `value`, found at `pointer`, with each leaf replaced by `{ value, source }`.
+    fn annotate(&mut self, value: &Json, pointer: &str) -> Json {
+        match value {
+            Json::Object(map) if !map.is_empty() => Json::Object(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), self.annotate(v, &format!("{}/{}", pointer, escape_token(k)))))
+                    .collect(),
+            ),
+            Json::Array(items) if !items.is_empty() => Json::Array(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| self.annotate(v, &format!("{}/{}", pointer, i)))
+                    .collect(),
+            ),
+            leaf => json!({ "value": leaf, "source": self.source(pointer, leaf) }),
+        }
+    }
+
+    fn source(&mut self, pointer: &str, leaf: &Json) -> String {
+        let tokens: Vec<String> = pointer
+            .split('/')
+            .skip(1)
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect();
+
+        // Front matter wins over data files, and is also exposed on its own as `page`.
+        let front_matter = match tokens.first().map(String::as_str) {
+            Some("book" | "renderer" | "chapter") => return "built-in".to_string(),
+            Some("page") => Some(&tokens[1..]),
+            _ => self
+                .page
+                .as_ref()
+                .and_then(|page| page.pointer(pointer))
+                .map(|_| &tokens[..]),
+        };
+        if let (Some(tokens), Some((file, content))) = (front_matter, &self.chapter) {
+            let page = self.page.as_ref().unwrap_or(&Json::Null);
+            return match locate(content, page, tokens, &mut self.key_patterns) {
+                Some(line) => format!("{}:{} (front matter)", file, line),
+                None => format!("{} (front matter)", file),
+            };
+        }
+
+        let Some(path) = self.renderer.origins().get(pointer).map(str::to_string) else {
+            return "<unknown>".to_string();
+        };
+        let resolved = data::resolve(&self.data_root, &path);
+        let file = self.files.entry(path.clone()).or_insert_with(|| {
+            let content = fs::read_to_string(&resolved).ok()?;
+            let value = Format::from_path(&resolved).parse(&content).ok()?;
+            Some((content, value))
+        });
+        let Some((content, value)) = file else {
+            return path;
+        };
+
+        // The file is mounted under some key: find the pointer within the file that leads to the
+        // same value.
+        let line = (0..=tokens.len())
+            .find(|&i| {
+                let local: String = tokens[i..].iter().map(|t| format!("/{}", escape_token(t))).collect();
+                value.pointer(&local) == Some(leaf)
+            })
+            .and_then(|i| locate(content, value, &tokens[i..], &mut self.key_patterns));
+        match line {
+            Some(line) => format!("{}:{}", path, line),
+            None => path,
+        }
+    }
+}
+
+/// Patterns finding a key in a data file, by key, compiled once for all the files.
+type KeyPatterns = BTreeMap<String, Regex>;
+
+/// The 1-based line of the last key in `tokens`, a pointer into `value` parsed from `content`,
+/// found by searching `content` for each key in turn. Within an array, the earlier items that have
+/// the next key are skipped over; a trailing index gives the line of the array's key.
+fn locate(content: &str, value: &Json, tokens: &[String], patterns: &mut KeyPatterns) -> Option<usize> {
+    let mut node = value;
+    let mut offset = 0;
+    let mut found = None;
+    let mut skip = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if let Json::Array(items) = node {
+            let idx: usize = token.parse().ok()?;
+            if let Some(next) = tokens.get(i + 1) {
+                skip = items[..idx.min(items.len())].iter().filter(|item| item.get(next).is_some()).count();
+            }
+            node = items.get(idx)?;
+            continue;
+        }
+        node = node.get(token)?;
+        let re = patterns.entry(token.clone()).or_insert_with(|| key_pattern(token));
+        let m = re.find_iter(&content[offset..]).nth(skip)?;
+        offset += m.end();
+        found = Some(offset);
+        skip = 0;
+    }
+    found.map(|end| content[..end].matches('\n').count() + 1)
+}
+
+/// Matches `key:` in JSON and YAML, `key =` and `[table.key]` in TOML, quoted or not.
+fn key_pattern(key: &str) -> Regex {
+    Regex::new(&format!(r#"(?m)(?:^|[\s{{,\[.])["']?{}["']?\s*[:=\].]"#, regex::escape(key))).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn line(format: Format, content: &str, pointer: &str) -> Option<usize> {
+        let value = format.parse(content).unwrap();
+        let tokens: Vec<String> = pointer.split('/').skip(1).map(str::to_string).collect();
+        locate(content, &value, &tokens, &mut BTreeMap::new())
+    }
+
+    #[test]
+    fn locates_keys_in_every_format() {
+        let json = "{\n  \"net\": {\n    \"name\": \"main\",\n    \"url\": \"https://a\"\n  }\n}\n";
+        assert_eq!(line(Format::Json, json, "/net/url"), Some(4));
+        assert_eq!(line(Format::Json, json, "/net"), Some(2));
+
+        let yaml = "name: top\nnet:\n  name: main\n  'url': https://a\n";
+        assert_eq!(line(Format::Yaml, yaml, "/net/name"), Some(3));
+        assert_eq!(line(Format::Yaml, yaml, "/net/url"), Some(4));
+
+        let toml = "name = \"top\"\n\n[net]\nname = \"main\"\n\n[net.\"rpc.v2\"]\nurl = \"https://a\"\n";
+        assert_eq!(line(Format::Toml, toml, "/net/name"), Some(4));
+        assert_eq!(line(Format::Toml, toml, "/net/rpc.v2/url"), Some(7));
+        assert_eq!(line(Format::Toml, toml, "/missing"), None);
+    }
+
+    #[test]
+    fn skips_earlier_array_items_with_the_same_key() {
+        let json = "{\"ops\": [\n  {\"name\": \"a\", \"url\": \"u\"},\n  {\"region\": \"eu\"},\n  {\"name\": \"c\"}\n]}";
+        assert_eq!(line(Format::Json, json, "/ops/0/name"), Some(2));
+        assert_eq!(line(Format::Json, json, "/ops/2/name"), Some(4));
+        assert_eq!(line(Format::Json, json, "/ops/1/region"), Some(3));
+        // A trailing index gives the line of the array's key.
+        assert_eq!(line(Format::Json, json, "/ops/2"), Some(1));
+
+        let yaml = "ops:\n  - name: a\n  - region: eu\n  - name: c\n";
+        assert_eq!(line(Format::Yaml, yaml, "/ops/2/name"), Some(4));
+    }
+
+    #[test]
+    fn finds_values_of_files_mounted_under_a_key() {
+        let dir = std::env::temp_dir().join(format!("mdbook-template-inspect-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.yaml"), "name: main\nrpc:\n  url: https://a\n").unwrap();
+        fs::write(dir.join("top.json"), "{\n  \"title\": \"t\"\n}").unwrap();
+        let config = mdbook::Config::from_str(
+            "[preprocessor.template]\npaths = [{ path = \"main.yaml\", as = \"networks.main\" }, \"top.json\"]",
+        )
+        .unwrap();
+        let renderer = Renderer::new(&config, &dir, "html").unwrap();
+        let mut sources = Sources {
+            renderer: &renderer,
+            data_root: dir.clone(),
+            page: None,
+            chapter: None,
+            files: BTreeMap::new(),
+            key_patterns: BTreeMap::new(),
+        };
+        let annotated = sources.annotate(&renderer.book_context(), "");
+        assert_eq!(
+            annotated["networks"]["main"]["rpc"]["url"],
+            json!({ "value": "https://a", "source": "main.yaml:3" })
+        );
+        assert_eq!(annotated["title"], json!({ "value": "t", "source": "top.json:2" }));
+        assert_eq!(annotated["renderer"]["source"], "built-in");
+    }
+}
@@ -0,0 +1,649 @@
+use crate::check::Diagnostic;
+use crate::merge::escape_token;
+use crate::render::{self, Renderer};
+use handlebars::template::{BlockParam, Parameter, Template, TemplateElement, TemplateMapping};
+use handlebars::Path as HbsPath;
+use mdbook::book::{BookItem, Chapter};
+use mdbook::errors::Error;
+use serde_json::Value as Json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Helpers whose parameters are expected to be missing at times, such as the condition of `if`.
+/// Their references count as uses but are never reported as undefined.
+const OPTIONAL_HELPERS: [&str; 3] = ["if", "unless", "default"];
+
+/// A variable path from the root of the context. `*` stands for every item of an array or value of
+/// an object, as iterated by `each`.
+type KeyPath = Vec<String>;
+
+/// What `mdbook-template keys` found.
+pub struct Report {
+    /// Data keys no chapter or partial references, attributed to the data file they came from.
+    pub unused: Vec<Diagnostic>,
+    /// References to keys the context of the chapter does not have.
+    pub undefined: Vec<Diagnostic>,
+    /// Chapters that could not be parsed, and whose references are therefore unknown.
+    pub skipped: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.unused.is_empty() && self.undefined.is_empty()
+    }
+}
+
+/// `mdbook-template keys <book-dir>`: prints the data keys no chapter uses and the references to
+/// keys that do not exist, one per line on stdout. Returns whether there were none.
+pub fn run(dir: &Path) -> Result<bool, Error> {
+    let book = render::load_book(dir)?;
+    let renderer = Renderer::new(&book.config, &book.root, "html")?;
+    let chapters = book.iter().filter_map(|item| match item {
+        BookItem::Chapter(ch) => Some(ch),
+        _ => None,
+    });
+    let report = report(&renderer, chapters, &book.config.book.src);
+
+    for diagnostic in report.unused.iter().chain(&report.undefined) {
+        println!("{}", diagnostic);
+    }
+    for diagnostic in &report.skipped {
+        eprintln!("{}", diagnostic);
+    }
+    eprintln!(
+        "{} unused key(s), {} undefined reference(s)",
+        report.unused.len(),
+        report.undefined.len()
+    );
+    Ok(report.is_clean())
+}
+
+/// Compares the variables referenced by `chapters` and the partials they include against the
+/// context. `src` is the book's source directory, used to name chapter files.
+///
+/// References are followed through `each`, `with`, block parameters, `../`, `@root` and partial
+/// contexts, and `query` with a literal pointer. The value a block iterates over or enters only
+/// counts as used through what the block reads from it. Values only reached through other helpers,
+/// such as `jsonpath` or `lookup` with a computed key, count as used when their parent is passed
+/// along.
+///
+/// Unlike the undefined variables `mdbook-template check` finds by rendering, references are found
+/// without rendering: those in branches a render skips, such as the body of an `if` that is false,
+/// are reported too, while the condition of `if` and `unless` and the first parameter of `default`
+/// never are. Like a render, references below an undefined `each` or `with` target are not
+/// reported, only the target itself.
+pub fn report<'a>(renderer: &Renderer, chapters: impl IntoIterator<Item = &'a Chapter>, src: &Path) -> Report {
+    let mut used = Vec::new();
+    let mut undefined = BTreeMap::new();
+    let mut skipped = Vec::new();
+
+    for ch in chapters {
+        // Draft chapters have no file.
+        let Some(source_path) = &ch.source_path else {
+            continue;
+        };
+        let file = src.join(source_path).display().to_string();
+        let parsed = renderer
+            .prepare(ch)
+            .and_then(|prepared| Ok((Template::compile(&prepared.template)?, prepared.context)));
+        let (template, context) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                skipped.push(Diagnostic {
+                    file,
+                    position: None,
+                    // `mdbook-template check` gives the details.
+                    message: format!("not checked: {}", format!("{:#}", e).lines().next().unwrap_or_default()),
+                });
+                continue;
+            }
+        };
+
+        let mut walker = Walker::new(renderer, file);
+        walker.walk(&template);
+        // Undefined `each` and `with` targets, whose bodies a render never reaches.
+        let mut undefined_targets: Vec<KeyPath> = Vec::new();
+        for reference in walker.references {
+            let dotted = reference.path.join(".");
+            let allowed = reference.optional
+                || renderer.is_allowed_path(&dotted)
+                || renderer.is_allowed_path(&reference.written);
+            let below_undefined = undefined_targets
+                .iter()
+                .any(|target| reference.path.len() > target.len() && reference.path.starts_with(target));
+            if !allowed && !below_undefined && !exists(&context, &reference.path) {
+                if !reference.covering {
+                    undefined_targets.push(reference.path.clone());
+                }
+                let mut message = format!("reference to undefined key `{}`", dotted);
+                if reference.written != dotted {
+                    message.push_str(&format!(" (written `{}`)", reference.written));
+                }
+                // Partials included from several chapters report each reference once.
+                undefined
+                    .entry((reference.file.clone(), reference.position))
+                    .or_insert(Diagnostic {
+                        file: reference.file,
+                        position: Some(reference.position),
+                        message,
+                    });
+            }
+            used.push((reference.path, reference.covering));
+        }
+    }
+
+    let mut unused = BTreeMap::new();
+    find_unused(renderer.data(), &mut Vec::new(), "", &used, &mut unused);
+    let unused = unused
+        .into_iter()
+        .map(|(dotted, pointer)| Diagnostic {
+            file: renderer.origins().get(&pointer).unwrap_or("<unknown>").to_string(),
+            position: None,
+            message: format!("unused key `{}`", dotted),
+        })
+        .collect();
+
+    Report {
+        unused,
+        undefined: undefined.into_values().collect(),
+        skipped,
+    }
+}
+
+/// A variable referenced by a template.
+struct Reference {
+    path: KeyPath,
+    /// The path as written in the template.
+    written: String,
+    file: String,
+    position: (usize, usize),
+    optional: bool,
+    /// Whether everything below the path counts as used. Not so for the target of a block, whose
+    /// body says which parts are.
+    covering: bool,
+}
+
+/// What relative paths resolve against within a block.
+struct Scope {
+    /// `None` when the block iterates over something other than a variable, such as a helper result.
+    base: Option<KeyPath>,
+    /// `as |name|` block parameters and partial hash parameters, with the path they stand for.
+    names: Vec<(String, Option<KeyPath>)>,
+}
+
+/// Collects the references of a template, tracking the block context the way Handlebars does:
+/// only `each`, `with` and partials open a new one.
+struct Walker<'a> {
+    renderer: &'a Renderer,
+    helpers: BTreeSet<&'static str>,
+    /// The file positions are reported in: the chapter, or the partial being walked.
+    file: String,
+    /// Innermost last.
+    scopes: Vec<Scope>,
+    /// Partials being walked, to stop on recursive inclusion.
+    partials: Vec<String>,
+    references: Vec<Reference>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(renderer: &'a Renderer, file: String) -> Walker<'a> {
+        Walker {
+            renderer,
+            helpers: renderer.helper_names(),
+            file,
+            scopes: vec![Scope {
+                base: Some(Vec::new()),
+                names: Vec::new(),
+            }],
+            partials: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    fn walk(&mut self, template: &Template) {
+        for (element, TemplateMapping(line, column)) in template.elements.iter().zip(&template.mapping) {
+            let position = (*line, *column);
+            match element {
+                TemplateElement::Expression(h) | TemplateElement::HtmlExpression(h) | TemplateElement::HelperBlock(h) => {
+                    let name = h.name.as_name().unwrap_or_default();
+                    let bare = h.params.is_empty() && h.hash.is_empty();
+                    // `{{title}}` reads a `title` variable rather than calling the helper, see `helpers::register`.
+                    let reads_variable = bare && !h.block && self.renderer.helper_reads_own_name(name);
+                    let is_helper = !bare || (self.helpers.contains(name) && !reads_variable);
+                    if !is_helper {
+                        // `{{name}}`, or `{{#name}} ... {{/name}}` rendering its body with the value.
+                        self.reference(name, position, h.block, !h.block);
+                        let inner = Scope {
+                            base: self.resolve(name),
+                            names: Vec::new(),
+                        };
+                        self.walk_block(h.template.as_ref(), Some(inner));
+                        self.walk_block(h.inverse.as_ref(), None);
+                        continue;
+                    }
+
+                    let params = match (name, h.params.split_first()) {
+                        ("each" | "with", Some((target, rest))) => {
+                            self.block_target(target, position);
+                            rest
+                        }
+                        _ => &h.params[..],
+                    };
+                    self.helper_args(name, params, h.hash.values(), position);
+                    let target = match h.params.first() {
+                        Some(Parameter::Path(path)) => self.resolve(path_raw(path)),
+                        _ => None,
+                    };
+                    let inner = match name {
+                        "each" => {
+                            let item = target.map(|mut p| {
+                                p.push("*".to_string());
+                                p
+                            });
+                            Some(Scope {
+                                names: block_params(h.block_param.as_ref(), &item),
+                                base: item,
+                            })
+                        }
+                        "with" => Some(Scope {
+                            names: block_params(h.block_param.as_ref(), &target),
+                            base: target,
+                        }),
+                        _ => None,
+                    };
+                    self.walk_block(h.template.as_ref(), inner);
+                    self.walk_block(h.inverse.as_ref(), None);
+                }
+                TemplateElement::PartialExpression(d) | TemplateElement::PartialBlock(d) => {
+                    let (context, rest) = match d.params.split_first() {
+                        Some((context, rest)) => (Some(context), rest),
+                        None => (None, &d.params[..]),
+                    };
+                    if let Some(context) = context {
+                        self.block_target(context, position);
+                    }
+                    for param in rest.iter().chain(d.hash.values()) {
+                        self.param(param, position, false);
+                    }
+                    let name = d.name.as_name().unwrap_or_default().to_string();
+                    let partial = self.renderer.handlebars().get_template(&name);
+                    if name.starts_with('@') || self.partials.contains(&name) {
+                        continue;
+                    }
+
+                    // A partial sees its parameter or the current context, plus its hash
+                    // parameters, and none of the enclosing block parameters.
+                    let base = match d.params.first() {
+                        Some(Parameter::Path(path)) => self.resolve(path_raw(path)),
+                        Some(_) => None,
+                        None => self.current_base(),
+                    };
+                    let names = d
+                        .hash
+                        .iter()
+                        .map(|(key, value)| {
+                            let target = match value {
+                                Parameter::Path(path) => self.resolve(path_raw(path)),
+                                _ => None,
+                            };
+                            (key.clone(), target)
+                        })
+                        .collect();
+                    let outer = std::mem::replace(&mut self.scopes, vec![Scope { base, names }]);
+                    match partial {
+                        Some(partial) => {
+                            let file = partial.name.clone().unwrap_or_else(|| self.file.clone());
+                            let outer_file = std::mem::replace(&mut self.file, file);
+                            self.partials.push(name);
+                            self.walk(partial);
+                            self.partials.pop();
+                            self.file = outer_file;
+                        }
+                        // A missing partial block renders its own content instead.
+                        None => {
+                            if let Some(t) = &d.template {
+                                self.walk(t);
+                            }
+                        }
+                    }
+                    self.scopes = outer;
+                }
+                TemplateElement::DecoratorExpression(d) | TemplateElement::DecoratorBlock(d) => {
+                    for param in d.params.iter().chain(d.hash.values()) {
+                        self.param(param, position, false);
+                    }
+                    if let Some(t) = &d.template {
+                        self.walk(t);
+                    }
+                }
+                TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+            }
+        }
+    }
+
+    /// Walks a block body, within `scope` if it opens one.
+    fn walk_block(&mut self, template: Option<&Template>, scope: Option<Scope>) {
+        let Some(template) = template else {
+            return;
+        };
+        let opened = scope.is_some();
+        self.scopes.extend(scope);
+        self.walk(template);
+        if opened {
+            self.scopes.pop();
+        }
+    }
+
+    fn helper_args<'p>(
+        &mut self,
+        name: &str,
+        params: &'p [Parameter],
+        hash: impl IntoIterator<Item = &'p Parameter>,
+        position: (usize, usize),
+    ) {
+        // `{{query "/portals/sui"}}` reads `portals.sui` from the root.
+        if let ([Parameter::Literal(Json::String(pointer))], "query") = (params, name) {
+            if let Some(tokens) = pointer.strip_prefix('/') {
+                let path = tokens.split('/').map(|t| t.replace("~1", "/").replace("~0", "~")).collect();
+                self.references.push(Reference {
+                    path,
+                    written: pointer.clone(),
+                    file: self.file.clone(),
+                    position,
+                    optional: false,
+                    covering: true,
+                });
+            }
+        }
+        let optional = OPTIONAL_HELPERS.contains(&name);
+        for param in params.iter().chain(hash) {
+            self.param(param, position, optional);
+        }
+    }
+
+    fn param(&mut self, param: &Parameter, position: (usize, usize), optional: bool) {
+        match param {
+            Parameter::Path(path) => self.reference(path_raw(path), position, optional, true),
+            Parameter::Subexpression(sub) if sub.is_helper() => {
+                let params = sub.params().cloned().unwrap_or_default();
+                let hash: Vec<Parameter> = sub.hash().map(|h| h.values().cloned().collect()).unwrap_or_default();
+                self.helper_args(sub.name(), &params, &hash, position);
+            }
+            Parameter::Subexpression(sub) => self.reference(sub.name(), position, optional, true),
+            Parameter::Name(_) | Parameter::Literal(_) => {}
+        }
+    }
+
+    /// The value `each`, `with` or a partial takes as its context.
+    fn block_target(&mut self, param: &Parameter, position: (usize, usize)) {
+        match param {
+            Parameter::Path(path) => self.reference(path_raw(path), position, false, false),
+            other => self.param(other, position, false),
+        }
+    }
+
+    fn reference(&mut self, written: &str, position: (usize, usize), optional: bool, covering: bool) {
+        if let Some(path) = self.resolve(written) {
+            self.references.push(Reference {
+                path,
+                written: written.to_string(),
+                file: self.file.clone(),
+                position,
+                optional,
+                covering,
+            });
+        }
+    }
+
+    fn current_base(&self) -> Option<KeyPath> {
+        self.scopes.last().and_then(|s| s.base.clone())
+    }
+
+    /// The path from the root that `written` refers to in the current scope, or `None` for local
+    /// variables such as `@index` and paths within a scope that cannot be followed.
+    fn resolve(&self, written: &str) -> Option<KeyPath> {
+        if let Some(rest) = written.strip_prefix("@root") {
+            return Some(split_path(rest.trim_start_matches(['.', '/'])));
+        }
+        let mut rest = written;
+        let mut up = 0;
+        while let Some(r) = rest.strip_prefix("../") {
+            up += 1;
+            rest = r;
+        }
+        let segments = split_path(rest);
+        if segments.first().is_some_and(|s| s.starts_with('@')) {
+            return None;
+        }
+
+        let scopes = &self.scopes[..self.scopes.len().saturating_sub(up).max(1)];
+        if let Some(first) = segments.first() {
+            for scope in scopes.iter().rev() {
+                if let Some((_, target)) = scope.names.iter().find(|(name, _)| name == first) {
+                    let mut path = target.clone()?;
+                    path.extend_from_slice(&segments[1..]);
+                    return Some(path);
+                }
+            }
+        }
+        let mut path = scopes.last()?.base.clone()?;
+        path.extend(segments);
+        Some(path)
+    }
+}
+
+/// The names bound by `as |item|` or `as |item key|` to the value at `target`; the second one is
+/// the index or key, which is not a context path.
+fn block_params(param: Option<&BlockParam>, target: &Option<KeyPath>) -> Vec<(String, Option<KeyPath>)> {
+    match param {
+        Some(BlockParam::Single(Parameter::Name(item))) => vec![(item.clone(), target.clone())],
+        Some(BlockParam::Pair((Parameter::Name(item), Parameter::Name(key)))) => {
+            vec![(item.clone(), target.clone()), (key.clone(), None)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn path_raw(path: &HbsPath) -> &str {
+    match path {
+        HbsPath::Relative((_, raw)) | HbsPath::Local((_, _, raw)) => raw,
+    }
+}
+
+/// Splits `a.b`, `a/b`, `a.[b c]` and `this.a` into segments.
+fn split_path(path: &str) -> KeyPath {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut bracketed = false;
+    for c in path.chars() {
+        match c {
+            '[' if current.is_empty() => bracketed = true,
+            ']' if bracketed => bracketed = false,
+            '.' | '/' if !bracketed => segments.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    segments.push(current);
+    segments.retain(|s| !s.is_empty() && s != "this");
+    segments
+}
+
+/// Whether `path` leads to a value. A `*` segment over an empty array or object counts as
+/// existing, since nothing is rendered from it.
+fn exists(value: &Json, path: &[String]) -> bool {
+    let Some((first, rest)) = path.split_first() else {
+        return true;
+    };
+    match value {
+        Json::Object(map) if first == "*" => map.is_empty() || map.values().any(|v| exists(v, rest)),
+        Json::Object(map) => map.get(first).is_some_and(|v| exists(v, rest)),
+        Json::Array(items) if first == "*" => items.is_empty() || items.iter().any(|v| exists(v, rest)),
+        Json::Array(items) => first
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| items.get(i))
+            .is_some_and(|v| exists(v, rest)),
+        _ => false,
+    }
+}
+
+/// Collects into `out` the outermost keys below `path` that no path in `used` reaches, as dotted
+/// path and JSON pointer. A used path covers everything below it if its flag is set, and only the
+/// key itself otherwise. Array items are looked at together, as `*`.
+fn find_unused(
+    value: &Json,
+    path: &mut KeyPath,
+    pointer: &str,
+    used: &[(KeyPath, bool)],
+    out: &mut BTreeMap<String, String>,
+) {
+    let matches = |a: &String, b: &String| a == "*" || b == "*" || a == b;
+    let mut below = false;
+    for (reference, covering) in used {
+        if reference.iter().zip(path.iter()).all(|(r, p)| matches(r, p)) {
+            if *covering && reference.len() <= path.len() {
+                return;
+            }
+            below |= reference.len() >= path.len();
+        }
+    }
+    if !below && !path.is_empty() {
+        out.entry(path.join(".")).or_insert_with(|| pointer.to_string());
+        return;
+    }
+
+    let children: Vec<(String, String, &Json)> = match value {
+        Json::Object(map) => map
+            .iter()
+            .map(|(k, v)| (k.clone(), format!("{}/{}", pointer, escape_token(k)), v))
+            .collect(),
+        Json::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, v)| ("*".to_string(), format!("{}/{}", pointer, i), v))
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (segment, child_pointer, child) in children {
+        path.push(segment);
+        find_unused(child, path, &child_pointer, used, out);
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use std::str::FromStr;
+
+    fn path(dotted: &str) -> KeyPath {
+        dotted.split('.').map(str::to_string).collect()
+    }
+
+    /// A renderer over `data`, written to a fresh book directory named after the test.
+    fn renderer(name: &str, data: &Json, settings: &str) -> Renderer {
+        let dir = std::env::temp_dir().join(format!("mdbook-template-keys-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("data.json"), data.to_string()).unwrap();
+        let settings = format!("[preprocessor.template]\npaths = [\"data.json\"]\n{}", settings);
+        Renderer::new(&mdbook::Config::from_str(&settings).unwrap(), &dir, "html").unwrap()
+    }
+
+    fn check(renderer: &Renderer, content: &str) -> (Vec<String>, Vec<String>) {
+        let ch = Chapter::new("a", content.to_string(), "a.md", Vec::new());
+        let report = report(renderer, [&ch], Path::new("src"));
+        let messages = |ds: Vec<Diagnostic>| ds.iter().map(Diagnostic::to_string).collect();
+        (messages(report.unused), messages(report.undefined))
+    }
+
+    #[test]
+    fn splits_paths() {
+        assert_eq!(split_path("a.b/c"), ["a", "b", "c"]);
+        assert_eq!(split_path("this.a.[b c].[d.e]"), ["a", "b c", "d.e"]);
+        assert!(split_path("this").is_empty());
+        assert!(split_path("").is_empty());
+    }
+
+    #[test]
+    fn checks_whether_paths_exist() {
+        let data = json!({ "ops": [{ "url": "a" }, { "name": "b" }], "empty": [], "map": { "x": { "url": 1 } } });
+        assert!(exists(&data, &[]));
+        assert!(exists(&data, &path("ops.0.url")));
+        assert!(!exists(&data, &path("ops.1.url")));
+        assert!(!exists(&data, &path("ops.2")));
+        assert!(exists(&data, &path("ops.*.name")));
+        assert!(!exists(&data, &path("ops.*.region")));
+        assert!(exists(&data, &path("empty.*.anything")));
+        assert!(exists(&data, &path("map.*.url")));
+        assert!(!exists(&data, &path("map.x.url.deeper")));
+    }
+
+    #[test]
+    fn finds_the_outermost_unused_keys() {
+        let data = json!({ "a": { "b": 1, "c": 2 }, "list": [{ "x": 1, "y": 2 }], "whole": { "d": 1 }, "spare": 0 });
+        let used = [
+            (path("a.b"), true),
+            (path("list"), false),
+            (path("list.*.x"), true),
+            (path("whole"), true),
+        ];
+        let mut unused = BTreeMap::new();
+        find_unused(&data, &mut Vec::new(), "", &used, &mut unused);
+        let unused: Vec<(&str, &str)> = unused.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(unused, [("a.c", "/a/c"), ("list.*.y", "/list/0/y"), ("spare", "/spare")]);
+    }
+
+    #[test]
+    fn follows_block_contexts() {
+        let data = json!({
+            "operators": [{ "name": "a", "url": "u", "region": "eu" }],
+            "portals": { "sui": { "url": "s" }, "other": { "url": "o" } },
+            "site": { "title": "t", "year": 2024 },
+            "spare": 1
+        });
+        let renderer = renderer("blocks", &data, "");
+        let content = "{{#each operators as |op|}}{{op.name}} {{url}} {{../portals.sui.url}}{{/each}}\n\
+                       {{#with site}}{{title}} {{@root.spare}}{{/with}}";
+        let (unused, undefined) = check(&renderer, content);
+        assert_eq!(
+            unused,
+            [
+                "data.json: unused key `operators.*.region`",
+                "data.json: unused key `portals.other`",
+                "data.json: unused key `site.year`",
+            ]
+        );
+        assert!(undefined.is_empty(), "{:?}", undefined);
+    }
+
+    #[test]
+    fn reports_undefined_targets_but_not_what_is_below_them() {
+        let renderer = renderer("undefined", &json!({ "ops": [{ "name": "a" }] }), "");
+        let content = "{{#each missing}}{{x}}{{/each}}\n{{#each ops}}{{region}}{{/each}}\n\
+                       {{#if nope}}{{also.missing}}{{/if}} {{default gone \"-\"}}";
+        let (unused, undefined) = check(&renderer, content);
+        assert_eq!(unused, ["data.json: unused key `ops.*.name`"]);
+        assert_eq!(
+            undefined,
+            [
+                "src/a.md:1:1: reference to undefined key `missing`",
+                "src/a.md:2:14: reference to undefined key `ops.*.region` (written `region`)",
+                "src/a.md:3:13: reference to undefined key `also.missing`",
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_query_pointers_and_allows_missing_paths() {
+        let data = json!({ "portals": { "sui.v2": { "url": "s" } }, "title": "t", "spare": 1 });
+        let renderer = renderer("query", &data, "helpers = true\nallow-missing = [\"draft.**\"]");
+        // A bare `{{title}}` reads the variable even with the `title` helper registered.
+        let content = "{{query \"/portals/sui.v2/url\"}} {{draft.title}} {{title}}";
+        let (unused, undefined) = check(&renderer, content);
+        assert_eq!(unused, ["data.json: unused key `spare`"]);
+        assert!(undefined.is_empty(), "{:?}", undefined);
+    }
+}
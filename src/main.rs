@@ -1,13 +1,33 @@
+mod check;
+mod config;
+mod data;
+mod delimiters;
+mod escape;
+mod format;
+mod frontmatter;
+mod glob;
+#[cfg(feature = "helpers")]
+mod helpers;
+mod inspect;
+mod keys;
+mod merge;
+mod metadata;
+mod partials;
+mod preview;
+mod protect;
+mod render;
+mod schema;
+mod strict;
+
 use anyhow::{Context as AnyhowContext, Result};
-use handlebars::Handlebars;
-use log::error;
+use config::OnError;
+use log::{error, warn};
 use mdbook::book::{Book, BookItem};
 use mdbook::errors::Error;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor, PreprocessorContext};
-use regex::Regex;
-use serde_json::Value as Json;
-use std::fs;
+use render::Renderer;
 use std::io;
+use std::path::Path;
 use std::process;
 
 struct Template;
@@ -18,73 +38,18 @@ impl Preprocessor for Template {
     }
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> std::result::Result<Book, Error> {
-        // Read config: [preprocessor.template] paths = ["docs/book/assets/operators.json", "docs/book/assets/portals.json"]
-        let cfg = ctx
-            .config
-            .get("preprocessor.template")
-            .and_then(|v| v.as_table())
-            .ok_or_else(|| Error::msg("missing [preprocessor.template] config with a `paths` array"))?;
-
-        let paths = cfg
-            .get("paths")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| Error::msg("missing `paths` array under [preprocessor.template]"))?;
-
-        // Merge all JSON files into a single template context (shallow merge on top-level keys).
-        let mut context = Json::Object(serde_json::Map::new());
-        for p in paths {
-            let path = p
-                .as_str()
-                .ok_or_else(|| Error::msg("paths entries must be strings"))?;
-            let txt = fs::read_to_string(path)
-                .with_context(|| format!("reading {}", path))
-                .map_err(Error::from)?;
-            let val: Json = serde_json::from_str(&txt)
-                .with_context(|| format!("parsing {}", path))
-                .map_err(Error::from)?;
-
-            if let Json::Object(map) = val {
-                if let Json::Object(ctx_map) = &mut context {
-                    for (k, v) in map {
-                        ctx_map.insert(k, v);
-                    }
-                }
+        let renderer = Renderer::new(&ctx.config, &ctx.root, &ctx.renderer)?;
+        if renderer.cfg.report_keys {
+            let chapters = book.iter().filter_map(|item| match item {
+                BookItem::Chapter(ch) => Some(ch),
+                _ => None,
+            });
+            let report = keys::report(&renderer, chapters, &ctx.config.book.src);
+            for diagnostic in report.unused.iter().chain(&report.undefined) {
+                warn!("{}", diagnostic);
             }
         }
-
-        // Render every chapter with Handlebars. Chapters without template tags pass through unchanged.
-        let hbs = Handlebars::new();
-        // Regex to match ${{ ... }} patterns
-        let dollar_brace_re = Regex::new(r"\$\{\{.*?\}\}").unwrap();
-
-        book.for_each_mut(|item| {
-            if let BookItem::Chapter(ch) = item {
-                // Store all ${{ ... }} patterns and replace with placeholders
-                let mut protected_patterns = Vec::new();
-                let mut placeholder_idx = 0;
-
-                let protected_content = dollar_brace_re.replace_all(&ch.content, |caps: &regex::Captures| {
-                    let full_match = caps.get(0).unwrap().as_str();
-                    protected_patterns.push(full_match.to_string());
-                    let placeholder = format!("__PROTECTED_PATTERN_{}__", placeholder_idx);
-                    placeholder_idx += 1;
-                    placeholder
-                });
-
-                match hbs.render_template(&protected_content, &context) {
-                    Ok(mut rendered) => {
-                        // Restore all protected patterns
-                        for (idx, pattern) in protected_patterns.iter().enumerate() {
-                            let placeholder = format!("__PROTECTED_PATTERN_{}__", idx);
-                            rendered = rendered.replace(&placeholder, pattern);
-                        }
-                        ch.content = rendered;
-                    },
-                    Err(e) => error!("Handlebars render error in {}: {}", ch.name, e),
-                }
-            }
-        });
-
+        render_chapters(&renderer, &mut book)?;
         Ok(book)
     }
 
@@ -93,6 +58,44 @@ impl Preprocessor for Template {
     }
 }
 
+/// Renders every chapter of `book` in place, handling failures as `on-error` says.
+fn render_chapters(renderer: &Renderer, book: &mut Book) -> std::result::Result<(), Error> {
+    // Chapters that failed to render, reported together under `on-error = "fail"`.
+    let mut failures = Vec::new();
+
+    // Render every chapter with Handlebars. Chapters without template tags pass through unchanged.
+    book.for_each_mut(|item| {
+        if let BookItem::Chapter(ch) = item {
+            match renderer.render(ch) {
+                Ok(rendered) => ch.content = rendered,
+                Err(e) => match renderer.cfg.on_error() {
+                    OnError::Fail => failures.push(format!("{}: {:#}", ch.name, e)),
+                    OnError::Warn => {
+                        warn!("Handlebars render error in {}: {:#}", ch.name, e);
+                        if let Ok(rendered) = renderer.render_unchecked(ch) {
+                            ch.content = rendered;
+                        }
+                    }
+                    OnError::KeepSource => warn!("Handlebars render error in {}: {:#}", ch.name, e),
+                    OnError::Blank => {
+                        error!("Handlebars render error in {}: {:#}", ch.name, e);
+                        ch.content.clear();
+                    }
+                },
+            }
+        }
+    });
+
+    if !failures.is_empty() {
+        return Err(Error::msg(format!(
+            "{} chapter(s) failed to render:\n  {}",
+            failures.len(),
+            failures.join("\n  ")
+        )));
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     env_logger::init();
 
@@ -109,6 +112,22 @@ fn main() -> Result<()> {
                 process::exit(1);
             }
         }
+        if cmd == "check" {
+            let dir = args.next().unwrap_or_else(|| ".".to_string());
+            let clean = check::run(Path::new(&dir)).map_err(|e| anyhow::anyhow!(e))?;
+            process::exit(if clean { 0 } else { 1 });
+        }
+        if cmd == "context" {
+            return inspect::run(args).map_err(|e| anyhow::anyhow!(e));
+        }
+        if cmd == "keys" {
+            let dir = args.next().unwrap_or_else(|| ".".to_string());
+            let clean = keys::run(Path::new(&dir)).map_err(|e| anyhow::anyhow!(e))?;
+            process::exit(if clean { 0 } else { 1 });
+        }
+        if cmd == "render" {
+            return preview::run(args).map_err(|e| anyhow::anyhow!(e));
+        }
     }
 
     // Parse input (context + book) from stdin using mdBook's helper.
@@ -119,3 +138,87 @@ fn main() -> Result<()> {
     serde_json::to_writer(io::stdout(), &processed).context("writing processed book to stdout")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook::book::Chapter;
+    use std::str::FromStr;
+
+    fn book(chapters: &[(&str, &str)]) -> Book {
+        let mut book = Book::new();
+        for (name, content) in chapters {
+            book.push_item(Chapter::new(name, content.to_string(), format!("{}.md", name), Vec::new()));
+        }
+        book
+    }
+
+    fn renderer(settings: &str) -> Renderer {
+        let config = mdbook::Config::from_str(&format!("[preprocessor.template]\n{}", settings)).unwrap();
+        Renderer::new(&config, Path::new("."), "html").unwrap()
+    }
+
+    fn contents(book: &Book) -> Vec<String> {
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(ch) => Some(ch.content.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fail_reports_every_failing_chapter() {
+        let mut book = book(&[("a", "{{#if}}"), ("b", "fine {{renderer}}"), ("c", "{{/each}}")]);
+        let err = render_chapters(&renderer(r#"on-error = "fail""#), &mut book).unwrap_err().to_string();
+        assert!(err.starts_with("2 chapter(s) failed to render:"), "{}", err);
+        assert!(err.contains("\n  a: ") && err.contains("\n  c: "), "{}", err);
+        assert_eq!(contents(&book)[1], "fine html");
+    }
+
+    #[test]
+    fn warn_renders_chapters_that_only_fail_the_strict_check() {
+        let mut book = book(&[("a", "x{{nope}}y"), ("b", "{{#if}}")]);
+        render_chapters(&renderer("strict = true\non-error = \"warn\""), &mut book).unwrap();
+        assert_eq!(contents(&book), ["xy", "{{#if}}"]);
+    }
+
+    #[test]
+    fn strict_fails_unless_on_error_is_set() {
+        let mut book = book(&[("a", "x{{nope}}y")]);
+        let err = render_chapters(&renderer("strict = true"), &mut book).unwrap_err().to_string();
+        assert!(err.contains("undefined variable `nope`"), "{}", err);
+        render_chapters(&renderer("on-error = \"warn\""), &mut book).unwrap();
+    }
+
+    #[test]
+    fn keep_source_and_blank() {
+        let mut kept = book(&[("a", "x{{nope}}y")]);
+        render_chapters(&renderer("strict = true\non-error = \"keep-source\""), &mut kept).unwrap();
+        assert_eq!(contents(&kept), ["x{{nope}}y"]);
+
+        let mut blanked = book(&[("a", "{{#if}}")]);
+        render_chapters(&renderer(r#"on-error = "blank""#), &mut blanked).unwrap();
+        assert_eq!(contents(&blanked), [""]);
+    }
+
+    #[test]
+    fn front_matter_is_opt_in() {
+        let source = "---\nTitle: a setext heading\n---\n\nHello {{renderer}}";
+        let mut plain = book(&[("a", source)]);
+        render_chapters(&renderer(""), &mut plain).unwrap();
+        assert_eq!(contents(&plain), ["---\nTitle: a setext heading\n---\n\nHello html"]);
+
+        let mut with_front_matter = book(&[("a", "---\ntitle: Intro\n---\n# {{page.title}}")]);
+        render_chapters(&renderer("front-matter = true"), &mut with_front_matter).unwrap();
+        assert_eq!(contents(&with_front_matter), ["# Intro"]);
+    }
+
+    #[cfg(feature = "helpers")]
+    #[test]
+    fn front_matter_keys_can_share_a_helper_name() {
+        let mut book = book(&[("a", "---\ntitle: Intro\n---\n# {{title}} {{title \"a b\"}}")]);
+        render_chapters(&renderer("front-matter = true\nhelpers = true\nstrict = true"), &mut book).unwrap();
+        assert_eq!(contents(&book), ["# Intro A B"]);
+    }
+}
@@ -0,0 +1,332 @@
+use log::info;
+use mdbook::errors::Error;
+use serde::Deserialize;
+use serde_json::Value as Json;
+use std::collections::BTreeMap;
+
+/// How a data file is combined with the files before it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Later files replace whole top-level keys.
+    #[default]
+    Shallow,
+    /// Objects are merged recursively; later files win on conflicting leaves.
+    Deep,
+    /// Objects are merged recursively; two files supplying the same leaf is an error.
+    ErrorOnConflict,
+}
+
+/// How arrays found at the same path are combined under deep merging.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArrayStrategy {
+    /// The later array replaces the earlier one.
+    #[default]
+    Replace,
+    /// Items of the later array are appended to the earlier one.
+    Append,
+    /// Objects sharing the same value for the merge key are merged; other items are appended.
+    MergeByKey,
+}
+
+/// Merge settings taken from the `[preprocessor.template]` config.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub strategy: Strategy,
+    pub arrays: ArrayStrategy,
+    /// Field identifying array items under `ArrayStrategy::MergeByKey`.
+    pub key: String,
+}
+
+/// Records which file supplied the value at each JSON pointer. A value not listed explicitly comes
+/// from the nearest listed ancestor.
+#[derive(Debug, Default)]
+pub struct Origins(BTreeMap<String, String>);
+
+impl Origins {
+    /// The file that supplied the value at `pointer`.
+    pub fn get(&self, pointer: &str) -> Option<&str> {
+        let mut p = pointer;
+        loop {
+            if let Some(source) = self.0.get(p) {
+                return Some(source);
+            }
+            p = &p[..p.rfind('/')?];
+        }
+    }
+
+    /// Marks `pointer` and everything below it as coming from `source`.
+    fn set(&mut self, pointer: &str, source: &str) {
+        let prefix = format!("{}/", pointer);
+        self.0.retain(|p, _| !p.starts_with(&prefix));
+        self.0.insert(pointer.to_string(), source.to_string());
+    }
+}
+
+/// Merges `incoming`, read from `source`, into `target` under the key path `key` (the root when
+/// empty), logging which file wins each conflict.
+///
+/// Under `Strategy::Shallow` the top-level keys of `incoming` replace those at the mount point.
+pub fn merge(
+    target: &mut Json,
+    key: &[&str],
+    incoming: Json,
+    source: &str,
+    opts: &Options,
+    origins: &mut Origins,
+) -> Result<(), Error> {
+    let mut pointer = String::new();
+    let mut slot = target;
+    for (i, segment) in key.iter().enumerate() {
+        if !slot.is_object() {
+            if opts.strategy == Strategy::ErrorOnConflict {
+                return Err(conflict(&pointer, source, origins));
+            }
+            log_conflict(&pointer, source, origins);
+            *slot = Json::Object(serde_json::Map::new());
+        }
+        pointer = format!("{}/{}", pointer, escape_token(segment));
+        let map = slot.as_object_mut().unwrap();
+        if !map.contains_key(*segment) {
+            if i + 1 == key.len() {
+                map.insert(segment.to_string(), incoming);
+                origins.set(&pointer, source);
+                return Ok(());
+            }
+            map.insert(segment.to_string(), Json::Object(serde_json::Map::new()));
+        }
+        slot = map.get_mut(*segment).unwrap();
+    }
+
+    match (opts.strategy, slot, incoming) {
+        (Strategy::Shallow, Json::Object(slot), Json::Object(incoming)) => {
+            for (k, v) in incoming {
+                let child = format!("{}/{}", pointer, escape_token(&k));
+                if slot.contains_key(&k) {
+                    log_conflict(&child, source, origins);
+                }
+                slot.insert(k, v);
+                origins.set(&child, source);
+            }
+            Ok(())
+        }
+        // Callers mount non-object files under a key; there is nothing to merge at the root.
+        (Strategy::Shallow, _, _) if key.is_empty() => Ok(()),
+        (Strategy::Shallow, slot, incoming) => {
+            log_conflict(&pointer, source, origins);
+            *slot = incoming;
+            origins.set(&pointer, source);
+            Ok(())
+        }
+        (_, slot, incoming) => merge_at(slot, incoming, &pointer, source, opts, origins),
+    }
+}
+
+fn merge_at(
+    target: &mut Json,
+    incoming: Json,
+    pointer: &str,
+    source: &str,
+    opts: &Options,
+    origins: &mut Origins,
+) -> Result<(), Error> {
+    match (target, incoming) {
+        (Json::Object(target), Json::Object(incoming)) => {
+            for (k, v) in incoming {
+                let child = format!("{}/{}", pointer, escape_token(&k));
+                match target.get_mut(&k) {
+                    Some(existing) => merge_at(existing, v, &child, source, opts, origins)?,
+                    None => {
+                        target.insert(k, v);
+                        origins.set(&child, source);
+                    }
+                }
+            }
+            Ok(())
+        }
+        (Json::Array(target), Json::Array(incoming)) if opts.arrays != ArrayStrategy::Replace => {
+            for item in incoming {
+                let existing = match (opts.arrays, &item) {
+                    (ArrayStrategy::MergeByKey, Json::Object(obj)) => obj
+                        .get(&opts.key)
+                        .and_then(|id| target.iter().position(|t| t.get(&opts.key) == Some(id))),
+                    _ => None,
+                };
+                match existing {
+                    Some(idx) => {
+                        let child = format!("{}/{}", pointer, idx);
+                        merge_at(&mut target[idx], item, &child, source, opts, origins)?;
+                    }
+                    None => {
+                        origins.set(&format!("{}/{}", pointer, target.len()), source);
+                        target.push(item);
+                    }
+                }
+            }
+            Ok(())
+        }
+        (target, incoming) if *target == incoming => Ok(()),
+        (target, incoming) => {
+            if opts.strategy == Strategy::ErrorOnConflict {
+                return Err(conflict(pointer, source, origins));
+            }
+            log_conflict(pointer, source, origins);
+            *target = incoming;
+            origins.set(pointer, source);
+            Ok(())
+        }
+    }
+}
+
+fn conflict(pointer: &str, source: &str, origins: &Origins) -> Error {
+    Error::msg(format!(
+        "conflicting values for `{}` in {} and {}",
+        pointer,
+        origins.get(pointer).unwrap_or("<unknown>"),
+        source
+    ))
+}
+
+fn log_conflict(pointer: &str, source: &str, origins: &Origins) {
+    info!(
+        "`{}`: value from {} overrides {}",
+        pointer,
+        source,
+        origins.get(pointer).unwrap_or("<unknown>")
+    );
+}
+
+/// Escapes a key for use as an RFC 6901 JSON pointer token.
+pub fn escape_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn options(strategy: Strategy, arrays: ArrayStrategy) -> Options {
+        Options {
+            strategy,
+            arrays,
+            key: "name".to_string(),
+        }
+    }
+
+    /// Merges each `(key, value, source)` in turn into an empty object.
+    fn merge_all(opts: &Options, files: Vec<(&[&str], Json, &str)>) -> Result<(Json, Origins), Error> {
+        let mut target = json!({});
+        let mut origins = Origins::default();
+        for (key, value, source) in files {
+            merge(&mut target, key, value, source, opts, &mut origins)?;
+        }
+        Ok((target, origins))
+    }
+
+    #[test]
+    fn shallow_replaces_top_level_keys() {
+        let opts = options(Strategy::Shallow, ArrayStrategy::Replace);
+        let (merged, _) = merge_all(
+            &opts,
+            vec![
+                (&[], json!({ "a": { "x": 1, "y": 2 }, "b": 1 }), "one.json"),
+                (&[], json!({ "a": { "x": 3 } }), "two.json"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(merged, json!({ "a": { "x": 3 }, "b": 1 }));
+    }
+
+    #[test]
+    fn deep_merges_objects_and_later_leaves_win() {
+        let opts = options(Strategy::Deep, ArrayStrategy::Replace);
+        let (merged, _) = merge_all(
+            &opts,
+            vec![
+                (&[], json!({ "a": { "x": 1, "y": 2, "list": [1, 2] } }), "one.json"),
+                (&[], json!({ "a": { "x": 3, "list": [3] } }), "two.json"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(merged, json!({ "a": { "x": 3, "y": 2, "list": [3] } }));
+    }
+
+    #[test]
+    fn error_on_conflict_names_both_files() {
+        let opts = options(Strategy::ErrorOnConflict, ArrayStrategy::Replace);
+        let same = merge_all(&opts, vec![(&[], json!({ "a": 1 }), "one.json"), (&[], json!({ "a": 1, "b": 2 }), "two.json")]);
+        assert_eq!(same.unwrap().0, json!({ "a": 1, "b": 2 }));
+
+        let err = merge_all(&opts, vec![(&[], json!({ "a": { "x": 1 } }), "one.json"), (&[], json!({ "a": { "x": 2 } }), "two.json")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "conflicting values for `/a/x` in one.json and two.json");
+    }
+
+    #[test]
+    fn error_on_conflict_covers_mount_points_below_a_scalar() {
+        let opts = options(Strategy::ErrorOnConflict, ArrayStrategy::Replace);
+        let err = merge_all(&opts, vec![(&[], json!({ "name": "Sui" }), "a.json"), (&["name", "x"], json!({ "y": 1 }), "c.json")])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "conflicting values for `/name` in a.json and c.json");
+
+        // Deep merging replaces the scalar, as it does for any conflicting leaf.
+        let opts = options(Strategy::Deep, ArrayStrategy::Replace);
+        let (merged, _) = merge_all(&opts, vec![(&[], json!({ "name": "Sui" }), "a.json"), (&["name", "x"], json!(1), "c.json")])
+            .unwrap();
+        assert_eq!(merged, json!({ "name": { "x": 1 } }));
+    }
+
+    #[test]
+    fn array_strategies() {
+        let files = || {
+            vec![
+                (&[] as &[&str], json!({ "ops": [{ "name": "a", "url": "1" }, { "name": "b" }] }), "one.json"),
+                (&[], json!({ "ops": [{ "name": "a", "url": "2", "region": "eu" }, { "name": "c" }] }), "two.json"),
+            ]
+        };
+        let merged = |arrays| merge_all(&options(Strategy::Deep, arrays), files()).unwrap().0;
+
+        assert_eq!(merged(ArrayStrategy::Replace)["ops"], json!([{ "name": "a", "url": "2", "region": "eu" }, { "name": "c" }]));
+        assert_eq!(
+            merged(ArrayStrategy::Append)["ops"],
+            json!([{ "name": "a", "url": "1" }, { "name": "b" }, { "name": "a", "url": "2", "region": "eu" }, { "name": "c" }])
+        );
+        assert_eq!(
+            merged(ArrayStrategy::MergeByKey)["ops"],
+            json!([{ "name": "a", "url": "2", "region": "eu" }, { "name": "b" }, { "name": "c" }])
+        );
+    }
+
+    #[test]
+    fn records_where_each_value_came_from() {
+        let opts = options(Strategy::Deep, ArrayStrategy::MergeByKey);
+        let (_, origins) = merge_all(
+            &opts,
+            vec![
+                (&[], json!({ "a": { "x": 1, "y": 2 }, "ops": [{ "name": "n", "v": 1 }] }), "one.json"),
+                (&[], json!({ "a": { "y": 3 }, "ops": [{ "name": "n", "w": 1 }, { "name": "m" }] }), "two.json"),
+                (&["mounted", "a/b"], json!({ "z": 1 }), "three.json"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(origins.get("/a/x"), Some("one.json"));
+        assert_eq!(origins.get("/a/y"), Some("two.json"));
+        assert_eq!(origins.get("/ops/0/v"), Some("one.json"));
+        assert_eq!(origins.get("/ops/0/w"), Some("two.json"));
+        assert_eq!(origins.get("/ops/1/name"), Some("two.json"));
+        assert_eq!(origins.get("/mounted/a~1b/z"), Some("three.json"));
+        assert_eq!(origins.get("/unknown"), None);
+    }
+
+    #[test]
+    fn setting_a_pointer_replaces_the_origins_below_it() {
+        let mut origins = Origins::default();
+        origins.set("/a/x", "one.json");
+        origins.set("/ab", "one.json");
+        origins.set("/a", "two.json");
+        assert_eq!(origins.get("/a/x"), Some("two.json"));
+        assert_eq!(origins.get("/ab"), Some("one.json"));
+    }
+}
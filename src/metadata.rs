@@ -0,0 +1,76 @@
+use mdbook::book::Chapter;
+use serde_json::{json, Value as Json};
+
+/// Keys injected into every chapter's context. They take precedence over data files and front matter.
+pub const RESERVED_KEYS: [&str; 4] = ["book", "renderer", "chapter", "page"];
+
+/// Book-wide metadata: `book` (title, authors, description, language, src) and `renderer`.
+pub fn book(config: &mdbook::Config, renderer: &str) -> Json {
+    let book = &config.book;
+    json!({
+        "book": {
+            "title": book.title,
+            "authors": book.authors,
+            "description": book.description,
+            "language": book.language,
+            "src": book.src.to_string_lossy(),
+        },
+        "renderer": renderer,
+    })
+}
+
+/// Metadata about the chapter being rendered, exposed as `chapter`.
+pub fn chapter(ch: &Chapter) -> Json {
+    json!({
+        "name": ch.name,
+        "path": ch.path.as_ref().map(|p| p.to_string_lossy()),
+        "source_path": ch.source_path.as_ref().map(|p| p.to_string_lossy()),
+        "number": ch.number.as_ref().map(|n| n.to_string()),
+        "parent_names": ch.parent_names,
+        "depth": ch.parent_names.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook::book::SectionNumber;
+    use std::str::FromStr;
+
+    #[test]
+    fn book_metadata_comes_from_book_toml() {
+        let config = mdbook::Config::from_str(
+            "[book]\ntitle = \"Guide\"\nauthors = [\"Ada\"]\nlanguage = \"en\"\nsrc = \"docs\"",
+        )
+        .unwrap();
+        assert_eq!(
+            book(&config, "html"),
+            json!({
+                "book": { "title": "Guide", "authors": ["Ada"], "description": null, "language": "en", "src": "docs" },
+                "renderer": "html",
+            })
+        );
+    }
+
+    #[test]
+    fn chapter_metadata() {
+        let mut ch = Chapter::new("Setup", String::new(), "guide/setup.md", vec!["Guide".to_string()]);
+        ch.number = Some(SectionNumber(vec![2, 1]));
+        assert_eq!(
+            chapter(&ch),
+            json!({
+                "name": "Setup",
+                "path": "guide/setup.md",
+                "source_path": "guide/setup.md",
+                "number": "2.1.",
+                "parent_names": ["Guide"],
+                "depth": 1,
+            })
+        );
+
+        let draft = Chapter::new_draft("Later", Vec::new());
+        assert_eq!(chapter(&draft)["path"], Json::Null);
+        assert_eq!(chapter(&draft)["number"], Json::Null);
+        assert_eq!(chapter(&draft)["depth"], 0);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::delimiters::Delimiters;
+use anyhow::Context as AnyhowContext;
+use handlebars::{Handlebars, Template};
+use log::{debug, warn};
+use mdbook::errors::Error;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// File extensions recognised as partials.
+const EXTENSIONS: [&str; 2] = ["hbs", "md"];
+
+/// Registers every `*.hbs` and `*.md` file under `dir` (relative to the book `root`) as a partial
+/// named after its path relative to `dir`, without the extension, e.g. `callouts/rpc-warning`.
+///
+/// Partials are compiled under their file path, so errors inside one report the file and the line
+/// within it. With custom `delimiters`, partials are translated like chapters are.
+pub fn register(hbs: &mut Handlebars, root: &Path, dir: &str, delimiters: Option<&Delimiters>) -> Result<(), Error> {
+    let resolved = root.join(dir);
+    if !resolved.is_dir() {
+        warn!("no partials registered ({} is not a directory)", resolved.display());
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(&resolved).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("reading partials from {}", resolved.display()))?;
+        let is_partial = entry
+            .path()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| EXTENSIONS.contains(&e));
+        if !entry.file_type().is_file() || !is_partial {
+            continue;
+        }
+
+        let rel = entry.path().strip_prefix(&resolved).unwrap_or(entry.path());
+        let name = rel
+            .with_extension("")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let file = Path::new(dir).join(rel).display().to_string();
+
+        let mut source = fs::read_to_string(entry.path()).with_context(|| format!("reading partial {}", file))?;
+        if let Some(delimiters) = delimiters {
+            source = delimiters.translate(&escape_literal_braces(&source));
+        }
+        let template = Template::compile_with_name(&source, file.clone())?;
+        debug!("registering partial `{}` from {}", name, file);
+        hbs.register_template(&name, template);
+    }
+    Ok(())
+}
+
+/// Escapes literal `{{` as `\{{` so that Handlebars outputs it unchanged.
+fn escape_literal_braces(source: &str) -> String {
+    source.replace("{{", "\\{{")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_literal_braces() {
+        assert_eq!(escape_literal_braces("{{a}} and {{{b}}}"), r"\{{a}} and \{{{b}}}");
+        let hbs = Handlebars::new();
+        let rendered = hbs.render_template(&escape_literal_braces("{{a}} x"), &serde_json::json!({ "a": 1 }));
+        assert_eq!(rendered.unwrap(), "{{a}} x");
+    }
+
+    /// A fresh book directory holding `files`, named after the test.
+    fn book(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("mdbook-template-partials-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn names_partials_after_their_path() {
+        let root = book(
+            "names",
+            &[
+                ("partials/note.md", "Note: {{text}}"),
+                ("partials/callouts/rpc-warning.hbs", "Warning!"),
+                ("partials/notes.txt", "ignored"),
+            ],
+        );
+        let mut hbs = Handlebars::new();
+        register(&mut hbs, &root, "partials", None).unwrap();
+        let mut names: Vec<&String> = hbs.get_templates().keys().collect();
+        names.sort();
+        assert_eq!(names, ["callouts/rpc-warning", "note"]);
+        let rendered = hbs.render_template("{{> note text=\"hi\"}} {{> callouts/rpc-warning}}", &());
+        assert_eq!(rendered.unwrap(), "Note: hi Warning!");
+    }
+
+    #[test]
+    fn reports_errors_in_the_partial_file() {
+        let root = book("errors", &[("partials/bad.hbs", "fine\n{{#if x}}{{/each}}\nmore\n")]);
+        let err = register(&mut Handlebars::new(), &root, "partials", None).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("\"partials/bad.hbs\":2:"), "{}", message);
+
+        let root = book("render-errors", &[("partials/broken.hbs", "fine\n{{#if}}{{/if}}\n")]);
+        let mut hbs = Handlebars::new();
+        register(&mut hbs, &root, "partials", None).unwrap();
+        let err = hbs.render_template("chapter\n{{> broken}}", &()).unwrap_err();
+        assert_eq!(err.template_name.as_deref(), Some("partials/broken.hbs"));
+        assert_eq!(err.line_no, Some(2));
+    }
+
+    #[test]
+    fn translates_partials_with_custom_delimiters() {
+        let root = book("delimiters", &[("partials/p.hbs", "[[x]] {{y}}")]);
+        let delimiters = Delimiters::try_from(["[[".to_string(), "]]".to_string()]).unwrap();
+        let mut hbs = Handlebars::new();
+        register(&mut hbs, &root, "partials", Some(&delimiters)).unwrap();
+        assert_eq!(hbs.render_template("{{> p}}", &serde_json::json!({ "x": 1 })).unwrap(), "1 {{y}}");
+    }
+
+    #[test]
+    fn a_missing_directory_registers_nothing() {
+        let root = book("missing", &[]);
+        let mut hbs = Handlebars::new();
+        register(&mut hbs, &root, "partials", None).unwrap();
+        assert!(hbs.get_templates().is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::format::Format;
+use crate::render::{self, Renderer};
+use anyhow::Context as AnyhowContext;
+use mdbook::book::{BookItem, Chapter};
+use mdbook::errors::Error;
+use serde_json::Value as Json;
+use std::fs;
+use std::path::PathBuf;
+
+const USAGE: &str = "usage: mdbook-template render <book-dir> [chapter-path ...] [--out <dir>] \
+                     [--data <key>=<value> ...] [--context <file>]";
+
+/// Arguments of `mdbook-template render`.
+struct Args {
+    book_dir: PathBuf,
+    /// Chapter source paths, relative to the book's `src` directory or to `book_dir`. All chapters
+    /// when empty.
+    chapters: Vec<PathBuf>,
+    out: Option<PathBuf>,
+    /// `--data key=value` overrides, in order.
+    data: Vec<(String, Json)>,
+    context: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, Error> {
+        let mut args = args.into_iter();
+        let mut positional = Vec::new();
+        let mut out = None;
+        let mut data = Vec::new();
+        let mut context = None;
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| {
+                args.next()
+                    .ok_or_else(|| Error::msg(format!("`{}` expects a value\n{}", flag, USAGE)))
+            };
+            match arg.as_str() {
+                "--out" => out = Some(PathBuf::from(value("--out")?)),
+                "--context" => context = Some(PathBuf::from(value("--context")?)),
+                "--data" => {
+                    let pair = value("--data")?;
+                    let (key, raw) = pair
+                        .split_once('=')
+                        .ok_or_else(|| Error::msg(format!("`--data` expects `key=value`, got `{}`", pair)))?;
+                    // `count=3` and `tags=["a"]` are JSON; anything that is not is taken as a string.
+                    let parsed = serde_json::from_str(raw).unwrap_or_else(|_| Json::String(raw.to_string()));
+                    data.push((key.to_string(), parsed));
+                }
+                flag if flag.starts_with("--") => {
+                    return Err(Error::msg(format!("unknown option `{}`\n{}", flag, USAGE)))
+                }
+                _ => positional.push(PathBuf::from(arg)),
+            }
+        }
+        if positional.is_empty() {
+            return Err(Error::msg(USAGE));
+        }
+        let book_dir = positional.remove(0);
+        Ok(Args {
+            book_dir,
+            chapters: positional,
+            out,
+            data,
+            context,
+        })
+    }
+}
+
+/// `mdbook-template render <book-dir> [chapter-path ...]`: renders chapters of the book the way the
+/// preprocessor would for the HTML renderer, without running mdBook.
+///
+/// The rendered Markdown goes to stdout, or with `--out <dir>` to files under that directory at the
+/// chapters' source paths. `--context <file>` merges a data file of any supported format over the
+/// context and `--data key.path=value` sets single values, both winning over everything else.
+/// Every chapter is attempted; failures are reported together.
+pub fn run(args: impl IntoIterator<Item = String>) -> Result<(), Error> {
+    let args = Args::parse(args)?;
+    let book = render::load_book(&args.book_dir)?;
+    let mut renderer = Renderer::new(&book.config, &book.root, "html")?;
+
+    if let Some(path) = &args.context {
+        let txt = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let extra = Format::from_path(path)
+            .parse(&txt)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        if !extra.is_object() {
+            return Err(Error::msg(format!("{} must hold an object at the top level", path.display())));
+        }
+        renderer.override_context(&[], extra, &path.display().to_string())?;
+    }
+    for (key, value) in args.data {
+        let path: Vec<&str> = key.split('.').collect();
+        renderer.override_context(&path, value, "--data")?;
+    }
+
+    let chapters: Vec<&Chapter> = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) if ch.source_path.is_some() => Some(ch),
+            _ => None,
+        })
+        .collect();
+    let selected: Vec<&Chapter> = if args.chapters.is_empty() {
+        chapters
+    } else {
+        args.chapters
+            .iter()
+            .map(|wanted| render::find_chapter(&book, wanted))
+            .collect::<Result<_, _>>()?
+    };
+
+    let mut failures = Vec::new();
+    for (i, ch) in selected.iter().enumerate() {
+        let source_path = ch.source_path.as_deref().unwrap();
+        let rendered = match renderer.render(ch) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                failures.push(format!("{}: {:#}", source_path.display(), e));
+                continue;
+            }
+        };
+        match &args.out {
+            Some(out) => {
+                let target = out.join(source_path);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+                }
+                fs::write(&target, rendered).with_context(|| format!("writing {}", target.display()))?;
+            }
+            None => {
+                // Chapters are told apart the way `head` does it.
+                if selected.len() > 1 {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("==> {} <==", source_path.display());
+                }
+                print!("{}", rendered);
+            }
+        }
+    }
+    if let Some(out) = &args.out {
+        eprintln!("wrote {} chapter(s) to {}", selected.len() - failures.len(), out.display());
+    }
+
+    if !failures.is_empty() {
+        return Err(Error::msg(format!(
+            "{} chapter(s) failed to render:\n  {}",
+            failures.len(),
+            failures.join("\n  ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(args: &[&str]) -> Result<Args, Error> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn parse_error(args: &[&str]) -> String {
+        match parse(args) {
+            Ok(_) => panic!("{:?} parsed", args),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_the_book_chapters_and_options() {
+        let args = parse(&["book", "intro.md", "--out", "out", "guide/setup.md", "--context", "ctx.yaml"]).unwrap();
+        assert_eq!(args.book_dir, PathBuf::from("book"));
+        assert_eq!(args.chapters, [PathBuf::from("intro.md"), PathBuf::from("guide/setup.md")]);
+        assert_eq!(args.out, Some(PathBuf::from("out")));
+        assert_eq!(args.context, Some(PathBuf::from("ctx.yaml")));
+        assert!(args.data.is_empty());
+
+        let args = parse(&["book"]).unwrap();
+        assert!(args.chapters.is_empty() && args.out.is_none() && args.context.is_none());
+    }
+
+    #[test]
+    fn data_values_are_json_or_strings() {
+        let args = parse(&[
+            "book",
+            "--data",
+            "count=3",
+            "--data",
+            "tags=[\"a\", 1]",
+            "--data",
+            "network.name=main net",
+            "--data",
+            "url=https://a.example/?x=1",
+            "--data",
+            "flag=true",
+            "--data",
+            "quoted=\"3\"",
+            "--data",
+            "empty=",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.data,
+            [
+                ("count".to_string(), json!(3)),
+                ("tags".to_string(), json!(["a", 1])),
+                ("network.name".to_string(), json!("main net")),
+                ("url".to_string(), json!("https://a.example/?x=1")),
+                ("flag".to_string(), json!(true)),
+                ("quoted".to_string(), json!("3")),
+                ("empty".to_string(), json!("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_arguments() {
+        assert_eq!(parse_error(&[]), USAGE);
+        assert_eq!(parse_error(&["--out", "out"]), USAGE);
+        assert_eq!(parse_error(&["book", "--out"]), format!("`--out` expects a value\n{}", USAGE));
+        assert_eq!(parse_error(&["book", "--data", "count"]), "`--data` expects `key=value`, got `count`");
+        assert_eq!(parse_error(&["book", "--verbose"]), format!("unknown option `--verbose`\n{}", USAGE));
+    }
+}
@@ -0,0 +1,228 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use regex::Regex;
+use std::ops::Range;
+
+/// Code block attribute that opts a fenced block into templating, e.g. ```` ```rust,template ````.
+const TEMPLATE_ATTR: &str = "template";
+
+/// Wraps placeholders; extended with more of the same character until it does not occur in the chapter.
+const MARKER: char = '\u{E000}';
+
+/// Text cut out of a chapter before rendering, to be put back afterwards.
+///
+/// Each protected span is replaced by a placeholder built from a marker that does not occur in the
+/// chapter, so no chapter text can be mistaken for one. Placeholders hold as many newlines as the
+/// span they replace, so line numbers reported by Handlebars still match the chapter source.
+#[derive(Debug)]
+pub struct Protected {
+    marker: String,
+    originals: Vec<String>,
+}
+
+impl Protected {
+    /// Prepares to protect spans of `content`.
+    pub fn new(content: &str) -> Protected {
+        let mut marker = MARKER.to_string();
+        while content.contains(&marker) {
+            marker.push(MARKER);
+        }
+        Protected {
+            marker,
+            originals: Vec::new(),
+        }
+    }
+
+    fn placeholder_for(&self, idx: usize, text: &str) -> String {
+        format!(
+            "{}{}{}{}",
+            self.marker,
+            idx,
+            "\n".repeat(text.matches('\n').count()),
+            self.marker
+        )
+    }
+
+    /// Stores `text` and returns the placeholder standing in for it.
+    fn placeholder(&mut self, text: &str) -> String {
+        let placeholder = self.placeholder_for(self.originals.len(), text);
+        self.originals.push(text.to_string());
+        placeholder
+    }
+
+    /// Replaces `<!-- template:off -->` ... `<!-- template:on -->` regions, markers included, with
+    /// placeholders. A region left open runs to the end of the chapter.
+    pub fn raw_regions(&mut self, content: &str) -> String {
+        let re = Regex::new(r"(?s)<!--\s*template:off\s*-->.*?(?:<!--\s*template:on\s*-->|\z)").unwrap();
+        self.patterns(content, &re)
+    }
+
+    /// Replaces every match of `re` in `content` with a placeholder.
+    pub fn patterns(&mut self, content: &str, re: &Regex) -> String {
+        re.replace_all(content, |caps: &regex::Captures| self.placeholder(&caps[0]))
+            .into_owned()
+    }
+
+    /// Replaces fenced and indented code blocks and inline code spans in `content` with placeholders.
+    ///
+    /// Fenced blocks whose info string carries the `template` attribute are left in place to be
+    /// rendered, with the attribute removed so that it does not reach the renderer.
+    pub fn code(&mut self, content: &str) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut last = 0;
+
+        for (range, opt_in) in code_spans(content) {
+            if range.start < last {
+                continue;
+            }
+            out.push_str(&content[last..range.start]);
+            let span = &content[range.clone()];
+            if opt_in {
+                out.push_str(&strip_template_attr(span));
+            } else {
+                out.push_str(&self.placeholder(span));
+            }
+            last = range.end;
+        }
+
+        out.push_str(&content[last..]);
+        out
+    }
+
+    /// Puts the protected text back in place of the placeholders in `rendered`.
+    pub fn restore(&self, mut rendered: String) -> String {
+        // Latest first, in case a span enclosed the placeholder of an earlier one.
+        for (idx, original) in self.originals.iter().enumerate().rev() {
+            rendered = rendered.replace(&self.placeholder_for(idx, original), original);
+        }
+        rendered
+    }
+}
+
+/// Byte ranges of the code blocks and inline code spans in `content`, flagged when a fenced block
+/// opts into templating.
+fn code_spans(content: &str) -> Vec<(Range<usize>, bool)> {
+    let opts = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+
+    Parser::new_ext(content, opts)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                Some((trim_trailing_newline(content, range), has_template_attr(&info)))
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)) => Some((trim_trailing_newline(content, range), false)),
+            Event::Code(_) => Some((range, false)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Block ranges include the final line break, which is better left outside the placeholder.
+fn trim_trailing_newline(content: &str, range: Range<usize>) -> Range<usize> {
+    let end = if content[range.clone()].ends_with('\n') { range.end - 1 } else { range.end };
+    range.start..end
+}
+
+fn info_attrs(info: &str) -> impl Iterator<Item = &str> {
+    info.split(|c: char| c == ',' || c.is_whitespace()).filter(|a| !a.is_empty())
+}
+
+fn has_template_attr(info: &str) -> bool {
+    info_attrs(info).any(|a| a == TEMPLATE_ATTR)
+}
+
+/// Removes the `template` attribute from the opening fence of a code block.
+fn strip_template_attr(block: &str) -> String {
+    let (fence_line, rest) = block.split_at(block.find('\n').unwrap_or(block.len()));
+    let marker_len = fence_line
+        .trim_start()
+        .chars()
+        .take_while(|&c| c == '`' || c == '~')
+        .count();
+    let indent = fence_line.len() - fence_line.trim_start().len();
+    let (fence, info) = fence_line.split_at(indent + marker_len);
+    let attrs: Vec<&str> = info_attrs(info).filter(|a| *a != TEMPLATE_ATTR).collect();
+    format!("{}{}{}", fence, attrs.join(","), rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_keep_line_counts_and_avoid_chapter_text() {
+        let mut protected = Protected::new("plain");
+        assert_eq!(protected.placeholder("a\nb\nc"), "\u{E000}0\n\n\u{E000}");
+        assert_eq!(protected.placeholder("d"), "\u{E000}1\u{E000}");
+
+        let protected = Protected::new("uses \u{E000} and \u{E000}\u{E000}");
+        assert_eq!(protected.marker, "\u{E000}".repeat(3));
+    }
+
+    #[test]
+    fn restores_text_that_looks_like_a_placeholder() {
+        let content = "\u{E000}0\u{E000} __PROTECTED_PATTERN_0__ `{{a}}` {{b}}";
+        let mut protected = Protected::new(content);
+        let template = protected.code(content);
+        assert_eq!(protected.originals, ["`{{a}}`"]);
+        let rendered = template.replace("{{b}}", "B");
+        assert_eq!(protected.restore(rendered), "\u{E000}0\u{E000} __PROTECTED_PATTERN_0__ `{{a}}` B");
+    }
+
+    #[test]
+    fn restores_nested_spans_latest_first() {
+        let content = "<<{{a}}>> {{b}}";
+        let mut protected = Protected::new(content);
+        let template = protected.patterns(content, &Regex::new(r"\{\{a\}\}").unwrap());
+        // The second span encloses the first one's placeholder.
+        let template = protected.patterns(&template, &Regex::new(r"<<[^>]*>>").unwrap());
+        assert_eq!(template, format!("{}1{} {{{{b}}}}", MARKER, MARKER));
+        assert_eq!(protected.restore(template), content);
+    }
+
+    #[test]
+    fn raw_regions_run_to_the_next_on_marker_or_the_end() {
+        let content = "{{a}}<!-- template:off -->{{b}}<!--template:on-->{{c}}<!-- template:off -->\n{{d}}";
+        let mut protected = Protected::new(content);
+        let template = protected.raw_regions(content);
+        assert_eq!(template, format!("{{{{a}}}}{m}0{m}{{{{c}}}}{m}1\n{m}", m = MARKER));
+        assert_eq!(
+            protected.originals,
+            ["<!-- template:off -->{{b}}<!--template:on-->", "<!-- template:off -->\n{{d}}"]
+        );
+    }
+
+    #[test]
+    fn finds_fenced_indented_and_inline_code() {
+        let content = "Text `{{a}}`.\n\n```rust\n{{b}}\n```\n\n    {{c}}\n\n~~~ sh,template\n{{d}}\n~~~\n";
+        let spans: Vec<(&str, bool)> =
+            code_spans(content).into_iter().map(|(range, opt_in)| (&content[range], opt_in)).collect();
+        assert_eq!(
+            spans,
+            [
+                ("`{{a}}`", false),
+                ("```rust\n{{b}}\n```", false),
+                ("{{c}}", false),
+                ("~~~ sh,template\n{{d}}\n~~~", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn template_blocks_are_rendered_without_the_attribute() {
+        let content = "```rust,template\n{{a}}\n```\n```rust\n{{b}}\n```\n";
+        let mut protected = Protected::new(content);
+        let template = protected.code(content);
+        assert_eq!(template, format!("```rust\n{{{{a}}}}\n```\n{m}0\n\n{m}\n", m = MARKER));
+    }
+
+    #[test]
+    fn strips_only_the_template_attribute() {
+        assert_eq!(strip_template_attr("```rust,template\nx\n```"), "```rust\nx\n```");
+        assert_eq!(strip_template_attr("  ~~~~ template ignore\nx"), "  ~~~~ignore\nx");
+        assert_eq!(strip_template_attr("```template,rust,templates"), "```rust,templates");
+    }
+}
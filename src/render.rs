@@ -0,0 +1,340 @@
+use crate::config::Config;
+use crate::merge::Origins;
+use crate::protect::Protected;
+use crate::{data, escape, frontmatter, glob, merge, metadata, partials, strict};
+use handlebars::Handlebars;
+use log::warn;
+use mdbook::book::{BookItem, Chapter};
+use mdbook::errors::Error;
+use mdbook::MDBook;
+use regex::Regex;
+use serde_json::Value as Json;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Helpers every Handlebars registry has.
+const BUILTIN_HELPERS: [&str; 17] = [
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte", "and", "or", "not",
+    "len",
+];
+
+/// Everything needed to render the chapters of one book: its settings, the data context and the
+/// configured Handlebars registry.
+pub struct Renderer {
+    pub cfg: Config,
+    context: Json,
+    /// The data file each value of `context` came from.
+    origins: Origins,
+    book_meta: Json,
+    hbs: Handlebars<'static>,
+    /// Only used to find undefined variables; output always comes from `hbs` so that paths allowed
+    /// by `allow-missing` still render as empty strings.
+    strict_hbs: Option<Handlebars<'static>>,
+    allow_missing: Vec<Regex>,
+    /// Patterns passed through untouched: the configured `protect` list, and mdBook's own link
+    /// directives, which look like block helpers but belong to its `links` preprocessor (including
+    /// their `\{{#include ...}}` escaped form, whose backslash Handlebars would eat).
+    protected_res: Vec<Regex>,
+    /// With custom delimiters, Handlebars' own `{{` is ordinary text.
+    literal_braces: Regex,
+    /// Values layered over every chapter's context, see [`Renderer::override_context`].
+    overrides: Json,
+}
+
+/// A chapter turned into a Handlebars template and the context to render it with.
+pub struct Prepared {
+    /// The chapter with front matter blanked out and protected text replaced by placeholders. Line
+    /// numbers match the chapter source.
+    pub template: String,
+    pub context: Json,
+    protected: Protected,
+    front_matter_lines: usize,
+}
+
+impl Renderer {
+    /// Reads the `[preprocessor.template]` settings and data files of the book at `root`, rendering
+    /// for `renderer`.
+    pub fn new(config: &mdbook::Config, root: &Path, renderer: &str) -> Result<Renderer, Error> {
+        let cfg = Config::from_book_config(config)?;
+
+        let (context, origins) = data::load(&cfg, root)?;
+        for key in metadata::RESERVED_KEYS {
+            if context.get(key).is_some() {
+                warn!("data key `{}` is shadowed by the built-in `{}` variable", key, key);
+            }
+        }
+        let book_meta = metadata::book(config, renderer);
+
+        let mut hbs = Handlebars::new();
+        escape::register(&mut hbs, cfg.escape);
+        if cfg.helpers {
+            #[cfg(feature = "helpers")]
+            crate::helpers::register(&mut hbs);
+            #[cfg(not(feature = "helpers"))]
+            warn!("`helpers = true` has no effect: mdbook-template was built without the `helpers` feature");
+        }
+        if let Some(dir) = &cfg.partials {
+            partials::register(&mut hbs, root, dir, cfg.delimiters.as_ref())?;
+        }
+        let strict_hbs = cfg.strict.then(|| strict_copy(&hbs));
+        let allow_missing = cfg
+            .allow_missing
+            .iter()
+            .map(|pattern| glob::to_regex(pattern, '.'))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut protected_res = cfg
+            .protect
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| Error::msg(format!("invalid `protect` pattern {:?}: {}", pattern, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        protected_res
+            .push(Regex::new(r"\\?\{\{\s*#(?:include|rustdoc_include|playground|title)\s[^}]*\}\}").unwrap());
+
+        Ok(Renderer {
+            cfg,
+            context,
+            origins,
+            book_meta,
+            hbs,
+            strict_hbs,
+            allow_missing,
+            protected_res,
+            literal_braces: Regex::new(r"\{\{").unwrap(),
+            overrides: Json::Object(Default::default()),
+        })
+    }
+
+    /// The registry chapters are rendered with.
+    pub fn handlebars(&self) -> &Handlebars<'static> {
+        &self.hbs
+    }
+
+    /// Merges `value`, taken from `source`, into the context of every chapter under the key path
+    /// `key` (the root when empty). Overrides win over data files, front matter and the built-in
+    /// variables.
+    pub fn override_context(&mut self, key: &[&str], value: Json, source: &str) -> Result<(), Error> {
+        merge::merge(&mut self.overrides, key, value, source, &deep_merge(), &mut Default::default())
+    }
+
+    /// Names of the helpers chapters can call.
+    pub fn helper_names(&self) -> BTreeSet<&'static str> {
+        #[allow(unused_mut)]
+        let mut names: BTreeSet<&'static str> = BUILTIN_HELPERS.into_iter().collect();
+        #[cfg(feature = "helpers")]
+        if self.cfg.helpers {
+            names.extend(crate::helpers::names());
+        }
+        names
+    }
+
+    /// Whether the helper `name` gives the variable of the same name when called without
+    /// parameters.
+    #[cfg_attr(not(feature = "helpers"), allow(unused_variables))]
+    pub fn helper_reads_own_name(&self, name: &str) -> bool {
+        #[cfg(feature = "helpers")]
+        if self.cfg.helpers {
+            return crate::helpers::reads_own_name(name);
+        }
+        false
+    }
+
+    /// The context merged from the data files, before front matter and built-in variables.
+    pub fn data(&self) -> &Json {
+        &self.context
+    }
+
+    /// The data context with the book-wide built-in variables, `book` and `renderer`: what every
+    /// chapter sees before its front matter and `page` and `chapter` variables are added.
+    pub fn book_context(&self) -> Json {
+        let mut context = self.context.clone();
+        if let (Json::Object(context), Json::Object(book_meta)) = (&mut context, &self.book_meta) {
+            context.extend(book_meta.clone());
+        }
+        context
+    }
+
+    /// The data file each value of [`Renderer::data`] came from.
+    pub fn origins(&self) -> &Origins {
+        &self.origins
+    }
+
+    /// Whether `allow-missing` lets the variable in `missing` be undefined.
+    pub fn is_allowed_missing(&self, missing: &strict::Missing) -> bool {
+        strict::is_allowed(missing, &self.allow_missing)
+    }
+
+    /// Whether `allow-missing` lets the dotted variable `path` be undefined.
+    pub fn is_allowed_path(&self, path: &str) -> bool {
+        self.allow_missing.iter().any(|re| re.is_match(path))
+    }
+
+    /// Splits off the chapter's front matter, builds its context and protects the text that must
+    /// not be templated.
+    pub fn prepare(&self, ch: &Chapter) -> Result<Prepared, Error> {
+        let split = if self.cfg.front_matter {
+            frontmatter::split(&ch.content)?
+        } else {
+            None
+        };
+        let (source, front_matter, front_matter_lines) = match split {
+            Some(split) => (split.body, split.front_matter, split.lines),
+            None => (ch.content.clone(), Json::Object(Default::default()), 0),
+        };
+        let mut context = chapter_context(&self.context, &self.book_meta, front_matter, ch)?;
+        merge::merge(&mut context, &[], self.overrides.clone(), "overrides", &deep_merge(), &mut Default::default())?;
+
+        // Replace raw regions, code and protected patterns with placeholders
+        let mut protected = Protected::new(&source);
+        let mut template = protected.raw_regions(&source);
+        if self.cfg.skip_code {
+            template = protected.code(&template);
+        }
+        for re in &self.protected_res {
+            template = protected.patterns(&template, re);
+        }
+        if let Some(delimiters) = &self.cfg.delimiters {
+            template = protected.patterns(&template, &self.literal_braces);
+            template = delimiters.translate(&template);
+        }
+
+        Ok(Prepared {
+            template,
+            context,
+            protected,
+            front_matter_lines,
+        })
+    }
+
+    /// Renders a chapter. Chapters without template tags come out unchanged.
+    pub fn render(&self, ch: &Chapter) -> Result<String, Error> {
+        self.render_with(ch, true)
+    }
+
+    /// Renders a chapter without the `strict` check, undefined values coming out empty.
+    pub fn render_unchecked(&self, ch: &Chapter) -> Result<String, Error> {
+        self.render_with(ch, false)
+    }
+
+    fn render_with(&self, ch: &Chapter, strict: bool) -> Result<String, Error> {
+        let prepared = self.prepare(ch)?;
+
+        if let Some(strict_hbs) = self.strict_hbs.as_ref().filter(|_| strict) {
+            strict::check(strict_hbs, &prepared.template, &prepared.context, &self.allow_missing)?;
+        }
+        // The message of a render error already includes its cause.
+        let rendered = self
+            .hbs
+            .render_template(&prepared.template, &prepared.context)
+            .map_err(|e| Error::msg(e.to_string()))?;
+
+        let rendered = prepared.protected.restore(rendered);
+        let rendered = frontmatter::strip_placeholder(rendered, prepared.front_matter_lines);
+        Ok(escape::finish(rendered, self.cfg.escape))
+    }
+}
+
+/// Loads the book at `dir` for the subcommands, without creating chapter files missing from
+/// `SUMMARY.md`.
+pub fn load_book(dir: &Path) -> Result<MDBook, Error> {
+    let config_file = dir.join("book.toml");
+    let mut config = if config_file.exists() {
+        mdbook::Config::from_disk(&config_file)?
+    } else {
+        mdbook::Config::default()
+    };
+    config.update_from_env();
+    config.build.create_missing = false;
+    MDBook::load_with_config(dir, config)
+}
+
+/// The chapter of `book` at `wanted`, a source path relative to the book's `src` directory or to
+/// the book directory.
+pub fn find_chapter<'b>(book: &'b MDBook, wanted: &Path) -> Result<&'b Chapter, Error> {
+    let src = &book.config.book.src;
+    book.iter()
+        .find_map(|item| match item {
+            BookItem::Chapter(ch) => {
+                let source_path = ch.source_path.as_deref()?;
+                (source_path == wanted || src.join(source_path) == wanted).then_some(ch)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| Error::msg(format!("no chapter `{}` in SUMMARY.md", wanted.display())))
+}
+
+/// A copy of `hbs` in strict mode.
+pub fn strict_copy(hbs: &Handlebars<'static>) -> Handlebars<'static> {
+    let mut strict_hbs = hbs.clone();
+    strict_hbs.set_strict_mode(true);
+    strict_hbs
+}
+
+/// Builds the context a chapter is rendered with: the data context with the chapter's front matter
+/// merged over it (front matter wins), then the built-in variables: `book_meta` (`book` and
+/// `renderer`), the front matter on its own as `page` and the chapter's metadata as `chapter`.
+fn chapter_context(context: &Json, book_meta: &Json, front_matter: Json, ch: &Chapter) -> Result<Json, Error> {
+    let mut chapter_context = context.clone();
+    let source = ch.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+    merge::merge(&mut chapter_context, &[], front_matter.clone(), &source, &deep_merge(), &mut Default::default())?;
+    if let (Json::Object(chapter_context), Json::Object(book_meta)) = (&mut chapter_context, book_meta) {
+        chapter_context.extend(book_meta.clone());
+    }
+    chapter_context["page"] = front_matter;
+    chapter_context["chapter"] = metadata::chapter(ch);
+    Ok(chapter_context)
+}
+
+/// Objects merged recursively, arrays replaced.
+fn deep_merge() -> merge::Options {
+    merge::Options {
+        strategy: merge::Strategy::Deep,
+        arrays: merge::ArrayStrategy::Replace,
+        key: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn render(settings: &str, content: &str) -> Result<String, Error> {
+        let config = mdbook::Config::from_str(&format!("[preprocessor.template]\n{}", settings)).unwrap();
+        let renderer = Renderer::new(&config, Path::new("."), "html")?;
+        renderer.render(&Chapter::new("a", content.to_string(), "a.md", Vec::new()))
+    }
+
+    #[test]
+    fn passes_link_directives_through() {
+        let source = "{{#include a.rs:2:5}} {{ #rustdoc_include b.rs:lib}} {{#playground c.rs editable}} \
+                      {{#title Setup}} {{renderer}}";
+        assert_eq!(
+            render("", source).unwrap(),
+            "{{#include a.rs:2:5}} {{ #rustdoc_include b.rs:lib}} {{#playground c.rs editable}} \
+             {{#title Setup}} html"
+        );
+        // The escaped form keeps its backslash for mdBook to remove.
+        assert_eq!(render("", r"\{{#include a.rs}} {{renderer}}").unwrap(), r"\{{#include a.rs}} html");
+        // Only mdBook's directives: other block helpers are still templates.
+        assert!(render("", "{{#includes a.rs}}").is_err());
+    }
+
+    #[test]
+    fn protects_configured_patterns() {
+        let settings = r#"protect = ['\$\{\{[^}]*\}\}']"#;
+        assert_eq!(render(settings, "run: ${{ matrix.os }} on {{renderer}}").unwrap(), "run: ${{ matrix.os }} on html");
+        let err = render(r#"protect = ['(']"#, "").unwrap_err().to_string();
+        assert!(err.starts_with("invalid `protect` pattern \"(\""), "{}", err);
+    }
+
+    #[test]
+    fn custom_delimiters_leave_handlebars_braces_alone() {
+        let settings = r#"delimiters = ["[[", "]]"]"#;
+        assert_eq!(
+            render(settings, "{{x}} [[renderer]] \\[[y]] ${{ env }}").unwrap(),
+            "{{x}} html [[y]] ${{ env }}"
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use crate::merge::escape_token;
+use regex::Regex;
+use serde_json::Value as Json;
+use std::fmt;
+
+/// A place where a data file does not match its schema.
+#[derive(Debug)]
+pub struct Violation {
+    /// JSON pointer to the offending value within the data file.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() { "(root)" } else { &self.pointer };
+        write!(f, "{}: {}", pointer, self.message)
+    }
+}
+
+/// Keywords [`validate`] checks.
+const KEYWORDS: [&str; 29] = [
+    "type", "enum", "const", "properties", "required", "additionalProperties", "patternProperties", "items",
+    "minItems", "maxItems", "uniqueItems", "minLength", "maxLength", "pattern", "minimum", "maximum",
+    "exclusiveMinimum", "exclusiveMaximum", "multipleOf", "allOf", "anyOf", "oneOf", "not", "if", "then", "else",
+    "$ref", "$defs", "definitions",
+];
+
+/// Keywords that only annotate a schema and never make a value invalid. The specification treats
+/// `format` as one of them unless a validator opts in.
+const ANNOTATIONS: [&str; 13] = [
+    "$schema", "$id", "$comment", "title", "description", "default", "examples", "format", "readOnly", "writeOnly",
+    "deprecated", "contentEncoding", "contentMediaType",
+];
+
+/// Checks `value` against a JSON Schema, returning every violation found.
+///
+/// Supports a practical subset of the specification: `type`, `enum`, `const`, `properties`,
+/// `required`, `additionalProperties`, `patternProperties`, `items`, `minItems`, `maxItems`,
+/// `uniqueItems`, `minLength`, `maxLength`, `pattern`, `minimum`, `maximum`, `exclusiveMinimum`,
+/// `exclusiveMaximum`, `multipleOf`, `allOf`, `anyOf`, `oneOf`, `not`, `if`/`then`/`else` and
+/// `$ref` to a pointer within the same schema (`#/$defs/...`), whose sibling keywords apply too.
+/// Schemas should be vetted with [`unsupported`] first, as anything else in them goes unchecked.
+pub fn validate(schema: &Json, value: &Json) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    Validator { root: schema }.check(schema, value, "", &[], &mut violations);
+    violations
+}
+
+struct Validator<'a> {
+    root: &'a Json,
+}
+
+impl<'a> Validator<'a> {
+    /// Checks `value`, found at `pointer`, against `schema`. `refs` are the references followed to get
+    /// to `schema` without moving on to another value, so a reference back to one of them is a cycle.
+    fn check(&self, schema: &'a Json, value: &Json, pointer: &str, refs: &[&'a str], out: &mut Vec<Violation>) {
+        let violation = |message: String| Violation {
+            pointer: pointer.to_string(),
+            message,
+        };
+        let schema = match schema {
+            Json::Bool(true) => return,
+            Json::Bool(false) => return out.push(violation("no value is allowed here".to_string())),
+            Json::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(Json::String(reference)) = schema.get("$ref") {
+            if refs.contains(&reference.as_str()) {
+                return out.push(violation(format!("schema reference cycle through `{}`", reference)));
+            }
+            match reference.strip_prefix('#').and_then(|p| self.root.pointer(p)) {
+                Some(target) => self.check(target, value, pointer, &[refs, &[reference.as_str()]].concat(), out),
+                None => out.push(violation(format!("schema reference `{}` cannot be resolved", reference))),
+            }
+        }
+
+        // Problems with the value itself, reported before those found below it.
+        let mut errors = Vec::new();
+        let mut nested = Vec::new();
+        let mut fail = |message: String| errors.push(message);
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Json::String(t) => vec![t.as_str()],
+                Json::Array(ts) => ts.iter().filter_map(Json::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+                return out.push(violation(format!("expected {}, found {}", types.join(" or "), type_name(value))));
+            }
+        }
+        if let Some(Json::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                let allowed: Vec<String> = allowed.iter().map(Json::to_string).collect();
+                fail(format!("{} is not one of {}", value, allowed.join(", ")));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                fail(format!("expected {}, found {}", expected, value));
+            }
+        }
+
+        match value {
+            Json::String(s) => {
+                let len = s.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Json::as_u64).filter(|min| len < *min) {
+                    fail(format!("is shorter than {} characters", min));
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Json::as_u64).filter(|max| len > *max) {
+                    fail(format!("is longer than {} characters", max));
+                }
+                if let Some(Json::String(pattern)) = schema.get("pattern") {
+                    match Regex::new(pattern) {
+                        Ok(re) if !re.is_match(s) => fail(format!("{:?} does not match the pattern {:?}", s, pattern)),
+                        Ok(_) => {}
+                        Err(e) => fail(format!("schema pattern {:?} is invalid: {}", pattern, e)),
+                    }
+                }
+            }
+            Json::Number(n) => {
+                let n = n.as_f64().unwrap_or(f64::NAN);
+                let bound = |key: &str| schema.get(key).and_then(Json::as_f64);
+                if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                    fail(format!("{} is less than the minimum of {}", n, min));
+                }
+                if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                    fail(format!("{} is greater than the maximum of {}", n, max));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                    fail(format!("{} is not greater than {}", n, min));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                    fail(format!("{} is not less than {}", n, max));
+                }
+                if let Some(step) = bound("multipleOf").filter(|step| *step > 0.0 && !is_multiple(n, *step)) {
+                    fail(format!("{} is not a multiple of {}", n, step));
+                }
+            }
+            Json::Array(items) => {
+                let len = items.len() as u64;
+                if let Some(min) = schema.get("minItems").and_then(Json::as_u64).filter(|min| len < *min) {
+                    fail(format!("has fewer than {} items", min));
+                }
+                if let Some(max) = schema.get("maxItems").and_then(Json::as_u64).filter(|max| len > *max) {
+                    fail(format!("has more than {} items", max));
+                }
+                if schema.get("uniqueItems") == Some(&Json::Bool(true)) {
+                    for (i, item) in items.iter().enumerate() {
+                        if items[..i].contains(item) {
+                            fail(format!("item {} repeats an earlier item", i));
+                        }
+                    }
+                }
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        self.check(item_schema, item, &format!("{}/{}", pointer, i), &[], &mut nested);
+                    }
+                }
+            }
+            Json::Object(map) => {
+                if let Some(Json::Array(required)) = schema.get("required") {
+                    for key in required.iter().filter_map(Json::as_str) {
+                        if !map.contains_key(key) {
+                            fail(format!("missing required property `{}`", key));
+                        }
+                    }
+                }
+                let properties = schema.get("properties").and_then(Json::as_object);
+                let patterns: Vec<(Regex, &Json)> = schema
+                    .get("patternProperties")
+                    .and_then(Json::as_object)
+                    .map(|p| p.iter().filter_map(|(re, s)| Some((Regex::new(re).ok()?, s))).collect())
+                    .unwrap_or_default();
+                for (key, item) in map {
+                    let child = format!("{}/{}", pointer, escape_token(key));
+                    let mut known = false;
+                    if let Some(property) = properties.and_then(|p| p.get(key)) {
+                        known = true;
+                        self.check(property, item, &child, &[], &mut nested);
+                    }
+                    for (re, property) in &patterns {
+                        if re.is_match(key) {
+                            known = true;
+                            self.check(property, item, &child, &[], &mut nested);
+                        }
+                    }
+                    match schema.get("additionalProperties") {
+                        Some(Json::Bool(false)) if !known => nested.push(Violation {
+                            pointer: child,
+                            message: "is not an allowed property".to_string(),
+                        }),
+                        Some(additional @ Json::Object(_)) if !known => self.check(additional, item, &child, &[], &mut nested),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Json::Array(all)) = schema.get("allOf") {
+            for sub in all {
+                self.check(sub, value, pointer, refs, &mut nested);
+            }
+        }
+        if let Some(Json::Array(any)) = schema.get("anyOf") {
+            if !any.iter().any(|sub| self.matches(sub, value, refs)) {
+                fail("does not match any of the `anyOf` schemas".to_string());
+            }
+        }
+        if let Some(Json::Array(one)) = schema.get("oneOf") {
+            let count = one.iter().filter(|sub| self.matches(sub, value, refs)).count();
+            if count != 1 {
+                fail(format!("matches {} of the `oneOf` schemas instead of exactly one", count));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.matches(not, value, refs) {
+                fail("matches the schema in `not`".to_string());
+            }
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.matches(condition, value, refs) { schema.get("then") } else { schema.get("else") };
+            if let Some(branch) = branch {
+                self.check(branch, value, pointer, refs, &mut nested);
+            }
+        }
+
+        out.extend(errors.into_iter().map(violation));
+        out.extend(nested);
+    }
+
+    fn matches(&self, schema: &'a Json, value: &Json, refs: &[&'a str]) -> bool {
+        let mut violations = Vec::new();
+        self.check(schema, value, "", refs, &mut violations);
+        violations.is_empty()
+    }
+}
+
+/// The parts of `schema` that [`validate`] would not check: keywords it does not support, values
+/// that should be schemas but are not, and invalid regexes. Each is described with the JSON
+/// pointer where it occurs, e.g. ``"`#/properties/tags` uses `contains`, which is not supported"``.
+pub fn unsupported(schema: &Json) -> Vec<String> {
+    let mut problems = Vec::new();
+    walk(schema, "#", &mut problems);
+    problems
+}
+
+fn walk(schema: &Json, pointer: &str, out: &mut Vec<String>) {
+    let schema = match schema {
+        Json::Bool(_) => return,
+        Json::Object(schema) => schema,
+        other => return out.push(format!("`{}` holds {} instead of a schema", pointer, type_name(other))),
+    };
+    for (key, value) in schema {
+        let at = format!("{}/{}", pointer, escape_token(key));
+        match key.as_str() {
+            "items" | "additionalProperties" | "not" | "if" | "then" | "else" => walk(value, &at, out),
+            "allOf" | "anyOf" | "oneOf" => match value {
+                Json::Array(subs) => {
+                    for (i, sub) in subs.iter().enumerate() {
+                        walk(sub, &format!("{}/{}", at, i), out);
+                    }
+                }
+                _ => out.push(format!("`{}` holds {} instead of an array of schemas", at, type_name(value))),
+            },
+            "properties" | "patternProperties" | "$defs" | "definitions" => match value {
+                Json::Object(subs) => {
+                    for (name, sub) in subs {
+                        if key == "patternProperties" {
+                            if let Err(e) = Regex::new(name) {
+                                out.push(format!("`{}` has an invalid pattern {:?}: {}", at, name, e));
+                            }
+                        }
+                        walk(sub, &format!("{}/{}", at, escape_token(name)), out);
+                    }
+                }
+                _ => out.push(format!("`{}` holds {} instead of an object of schemas", at, type_name(value))),
+            },
+            "pattern" => {
+                if let Some(Err(e)) = value.as_str().map(Regex::new) {
+                    out.push(format!("`{}` is an invalid pattern: {}", at, e));
+                }
+            }
+            key if KEYWORDS.contains(&key) || ANNOTATIONS.contains(&key) => {}
+            key => out.push(format!("`{}` uses `{}`, which is not supported", pointer, key)),
+        }
+    }
+}
+
+/// Whether `n` is a whole multiple of `step`, allowing for floating-point error so that `0.3` is a
+/// multiple of `0.1`.
+fn is_multiple(n: f64, step: f64) -> bool {
+    let quotient = n / step;
+    (quotient - quotient.round()).abs() < 1e-9
+}
+
+fn has_type(value: &Json, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Json) -> &'static str {
+    match value {
+        Json::Null => "null",
+        Json::Bool(_) => "boolean",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages(schema: Json, value: Json) -> Vec<String> {
+        validate(&schema, &value).iter().map(Violation::to_string).collect()
+    }
+
+    #[test]
+    fn types_and_values() {
+        assert_eq!(messages(json!({ "type": "string" }), json!(1)), ["(root): expected string, found number"]);
+        assert!(messages(json!({ "type": ["integer", "null"] }), json!(3)).is_empty());
+        assert_eq!(messages(json!({ "type": "integer" }), json!(1.5)), ["(root): expected integer, found number"]);
+        assert_eq!(messages(json!({ "enum": ["a", "b"] }), json!("c")), [r#"(root): "c" is not one of "a", "b""#]);
+        assert_eq!(messages(json!({ "const": 1 }), json!(2)), ["(root): expected 1, found 2"]);
+        assert_eq!(messages(json!(false), json!(null)), ["(root): no value is allowed here"]);
+        assert!(messages(json!(true), json!(null)).is_empty());
+    }
+
+    #[test]
+    fn strings_and_numbers() {
+        let schema = json!({ "minLength": 2, "maxLength": 3, "pattern": "^[a-z]+$" });
+        assert_eq!(
+            messages(schema.clone(), json!("A")),
+            ["(root): is shorter than 2 characters", r#"(root): "A" does not match the pattern "^[a-z]+$""#]
+        );
+        assert_eq!(messages(schema, json!("abcd")), ["(root): is longer than 3 characters"]);
+
+        let schema = json!({ "minimum": 1, "maximum": 10, "exclusiveMaximum": 10 });
+        assert_eq!(messages(schema.clone(), json!(0)), ["(root): 0 is less than the minimum of 1"]);
+        assert_eq!(messages(schema, json!(10)), ["(root): 10 is not less than 10"]);
+    }
+
+    #[test]
+    fn arrays() {
+        let schema = json!({ "minItems": 1, "maxItems": 3, "uniqueItems": true, "items": { "type": "number" } });
+        assert_eq!(messages(schema.clone(), json!([])), ["(root): has fewer than 1 items"]);
+        assert_eq!(
+            messages(schema, json!([1, "x", 1])),
+            ["(root): item 2 repeats an earlier item", "/1: expected number, found string"]
+        );
+    }
+
+    #[test]
+    fn objects() {
+        let schema = json!({
+            "required": ["name"],
+            "properties": { "name": { "type": "string" } },
+            "patternProperties": { "^x-": { "type": "boolean" } },
+            "additionalProperties": false
+        });
+        assert!(messages(schema.clone(), json!({ "name": "a", "x-beta": true })).is_empty());
+        assert_eq!(
+            messages(schema, json!({ "x-beta": 1, "a/b": 0 })),
+            [
+                "(root): missing required property `name`",
+                "/a~1b: is not an allowed property",
+                "/x-beta: expected boolean, found number"
+            ]
+        );
+    }
+
+    #[test]
+    fn combinators() {
+        let schema = json!({ "anyOf": [{ "type": "string" }, { "type": "number" }] });
+        assert_eq!(messages(schema, json!(null)), ["(root): does not match any of the `anyOf` schemas"]);
+        let schema = json!({ "oneOf": [{ "type": "number" }, { "minimum": 0 }] });
+        assert_eq!(messages(schema, json!(1)), ["(root): matches 2 of the `oneOf` schemas instead of exactly one"]);
+        assert_eq!(messages(json!({ "not": { "const": 1 } }), json!(1)), ["(root): matches the schema in `not`"]);
+        let schema = json!({ "allOf": [{ "minimum": 0 }, { "maximum": 5 }] });
+        assert_eq!(messages(schema, json!(6)), ["(root): 6 is greater than the maximum of 5"]);
+    }
+
+    #[test]
+    fn references() {
+        let schema = json!({
+            "$defs": { "node": { "type": "object", "properties": { "next": { "$ref": "#/$defs/node" } } } },
+            "$ref": "#/$defs/node"
+        });
+        assert!(messages(schema.clone(), json!({ "next": { "next": {} } })).is_empty());
+        assert_eq!(messages(schema, json!({ "next": { "next": 1 } })), ["/next/next: expected object, found number"]);
+        assert_eq!(
+            messages(json!({ "$ref": "#/$defs/missing" }), json!(1)),
+            ["(root): schema reference `#/$defs/missing` cannot be resolved"]
+        );
+    }
+
+    #[test]
+    fn reports_reference_cycles() {
+        assert_eq!(messages(json!({ "$ref": "#" }), json!(1)), ["(root): schema reference cycle through `#`"]);
+
+        let schema = json!({
+            "$defs": { "a": { "$ref": "#/$defs/b" }, "b": { "allOf": [{ "$ref": "#/$defs/a" }] } },
+            "properties": { "x": { "$ref": "#/$defs/a" } }
+        });
+        assert_eq!(messages(schema, json!({ "x": 1 })), ["/x: schema reference cycle through `#/$defs/a`"]);
+    }
+
+    #[test]
+    fn applies_the_keywords_next_to_a_reference() {
+        let schema = json!({ "$defs": { "o": { "type": "object" } }, "$ref": "#/$defs/o", "required": ["zzz"] });
+        assert_eq!(messages(schema.clone(), json!({})), ["(root): missing required property `zzz`"]);
+        assert_eq!(messages(schema, json!(1)), ["(root): expected object, found number"]);
+    }
+
+    #[test]
+    fn multiples_and_conditions() {
+        assert_eq!(messages(json!({ "multipleOf": 2 }), json!(5)), ["(root): 5 is not a multiple of 2"]);
+        assert!(messages(json!({ "multipleOf": 0.1 }), json!(0.3)).is_empty());
+
+        let schema = json!({ "multipleOf": 2, "if": { "type": "number" }, "then": { "minimum": 100 }, "else": false });
+        assert_eq!(
+            messages(schema.clone(), json!(5)),
+            ["(root): 5 is not a multiple of 2", "(root): 5 is less than the minimum of 100"]
+        );
+        assert!(messages(schema.clone(), json!(200)).is_empty());
+        assert_eq!(messages(schema, json!("x")), ["(root): no value is allowed here"]);
+        assert!(messages(json!({ "if": { "type": "string" }, "then": false }), json!(1)).is_empty());
+    }
+
+    #[test]
+    fn lists_what_the_validator_cannot_check() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Operators",
+            "properties": {
+                "url": { "type": "string", "format": "uri" },
+                "tags": { "contains": { "const": "rpc" }, "items": [{ "type": "string" }] },
+                "name": { "pattern": "(" }
+            },
+            "patternProperties": { "[": true },
+            "allOf": { "type": "object" },
+            "$defs": { "x": { "dependentRequired": {} } },
+            "unevaluatedProperties": false
+        });
+        let problems = unsupported(&schema);
+        // Regex errors span several lines.
+        let first_lines: Vec<&str> = problems.iter().map(|p| p.lines().next().unwrap()).collect();
+        assert_eq!(
+            first_lines,
+            [
+                "`#/$defs/x` uses `dependentRequired`, which is not supported",
+                "`#/allOf` holds object instead of an array of schemas",
+                "`#/patternProperties` has an invalid pattern \"[\": regex parse error:",
+                "`#/properties/name/pattern` is an invalid pattern: regex parse error:",
+                "`#/properties/tags` uses `contains`, which is not supported",
+                "`#/properties/tags/items` holds array instead of a schema",
+                "`#` uses `unevaluatedProperties`, which is not supported",
+            ]
+        );
+        assert!(unsupported(&json!({ "$defs": { "a": { "enum": [{ "contains": 1 }] } } })).is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+use handlebars::{Handlebars, RenderErrorReason};
+use mdbook::errors::Error;
+use regex::Regex;
+use serde_json::Value as Json;
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Upper bound on re-renders while collecting undefined variables from a single chapter.
+const MAX_PASSES: usize = 256;
+
+/// The opening tag of a block, capturing the helper name.
+static BLOCK_OPEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\{\{~?\s*#\s*([^\s}~]+)").unwrap());
+/// Any opening or closing block tag, capturing `#` or `/` and the helper name.
+static BLOCK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{~?\s*([#/])\s*([^\s}~]+)").unwrap());
+
+/// An expression that referenced a variable missing from the context.
+#[derive(Debug)]
+pub struct Missing {
+    /// The variable path as written, e.g. `operators.mainet.url`; `None` for helpers like `lookup`.
+    pub path: Option<String>,
+    /// The full `{{ ... }}` expression containing the reference.
+    pub expression: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Missing {
+    /// The path with a leading `this.` or `./` removed, as matched against `allow-missing`.
+    pub fn normalized_path(&self) -> Option<&str> {
+        self.path.as_deref().map(|p| {
+            p.strip_prefix("this.")
+                .or_else(|| p.strip_prefix("./"))
+                .unwrap_or(p)
+        })
+    }
+
+    /// What is missing, without the position.
+    pub fn message(&self) -> String {
+        match &self.path {
+            Some(path) => format!("undefined variable `{}` in `{}`", path, self.expression),
+            None => format!("undefined value in `{}`", self.expression),
+        }
+    }
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message(), self.line, self.column)
+    }
+}
+
+/// What [`find_missing`] found.
+pub struct Search {
+    pub missing: Vec<Missing>,
+    /// The error other than a missing variable that ended the search, if any.
+    pub error: Option<handlebars::RenderError>,
+    /// Whether the search gave up after [`MAX_PASSES`] variables with more left to find.
+    pub truncated: bool,
+}
+
+impl Search {
+    /// The line reported after the variables when the search was cut short.
+    pub fn truncated_message() -> String {
+        format!("... and more undefined variables; only the first {} are reported", MAX_PASSES)
+    }
+}
+
+/// Fails with one line per undefined variable in `template`, skipping paths matched by `allowed`.
+pub fn check(hbs: &Handlebars, template: &str, data: &Json, allowed: &[Regex]) -> Result<(), Error> {
+    let search = find_missing(hbs, template, data);
+    if let Some(e) = search.error {
+        return Err(Error::msg(e.to_string()));
+    }
+    let mut missing: Vec<String> = search
+        .missing
+        .into_iter()
+        .filter(|m| !is_allowed(m, allowed))
+        .map(|m| m.to_string())
+        .collect();
+    if search.truncated {
+        missing.push(Search::truncated_message());
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::msg(missing.join("\n    ")))
+    }
+}
+
+/// Whether one of the `allowed` patterns matches the path of `missing`.
+pub fn is_allowed(missing: &Missing, allowed: &[Regex]) -> bool {
+    missing
+        .normalized_path()
+        .is_some_and(|p| allowed.iter().any(|re| re.is_match(p)))
+}
+
+/// Collects every undefined variable referenced by `template`.
+///
+/// Handlebars strict mode stops at the first missing variable, so each reported expression is
+/// neutralised in a scratch copy of the template and rendering is retried until it succeeds.
+/// The first error other than a missing variable ends the search and is returned alongside the
+/// variables found before it. The search stops after [`MAX_PASSES`] variables, each costing a
+/// render of the whole template. `hbs` must have strict mode enabled.
+pub fn find_missing(hbs: &Handlebars, template: &str, data: &Json) -> Search {
+    let mut source = template.to_string();
+    let mut search = Search {
+        missing: Vec::new(),
+        error: None,
+        truncated: false,
+    };
+
+    loop {
+        let err = match hbs.render_template(&source, data) {
+            Ok(_) => break,
+            Err(e) => e,
+        };
+        let path = match err.reason() {
+            RenderErrorReason::MissingVariable(path) => path.clone(),
+            _ => {
+                search.error = Some(err);
+                break;
+            }
+        };
+        if search.missing.len() == MAX_PASSES {
+            search.truncated = true;
+            break;
+        }
+        // Inside a partial the position refers to the partial's own source, not to `template`.
+        let span = match (&err.template_name, err.line_no, err.column_no) {
+            (None, Some(line), Some(column)) => expression_span(&source, line, column).map(|span| (span, line, column)),
+            _ => None,
+        };
+        let Some(((start, end), line, column)) = span else {
+            search.error = Some(err);
+            break;
+        };
+
+        let expression = source[start..end].to_string();
+        let neutralized = neutralize(&mut source, start, end);
+        search.missing.push(Missing {
+            path,
+            expression,
+            line,
+            column,
+        });
+        if !neutralized {
+            break;
+        }
+    }
+
+    search
+}
+
+/// Byte range of the `{{ ... }}` expression starting at the 1-based `line` and `column`.
+fn expression_span(source: &str, line: usize, column: usize) -> Option<(usize, usize)> {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        source.match_indices('\n').nth(line - 2)?.0 + 1
+    };
+    let start = line_start + source[line_start..].char_indices().nth(column.checked_sub(1)?)?.0;
+    if !source[start..].starts_with("{{") {
+        return None;
+    }
+
+    let close = if source[start..].starts_with("{{{") { "}}}" } else { "}}" };
+    let end = start + source[start..].find(close)? + close.len();
+    Some((start, end))
+}
+
+/// Rewrites the expression at `start..end` so that it no longer references a missing variable.
+///
+/// Plain expressions are blanked out. Block helpers are turned into `{{#if 0}} ... {{/if}}` so the
+/// template stays balanced and the block body, which may only make sense for the missing value, is
+/// skipped; a block whose tags are too short for that is blanked out whole. Rewrites keep every
+/// line the same length so that later errors point at their place in the original template.
+/// Returns false if the expression could not be rewritten.
+fn neutralize(source: &mut String, start: usize, end: usize) -> bool {
+    let Some(name) = BLOCK_OPEN.captures(&source[start..end]).map(|c| c[1].to_string()) else {
+        let blank = same_shape(&source[start..end], "", "").unwrap();
+        source.replace_range(start..end, &blank);
+        return true;
+    };
+
+    let mut depth = 0;
+    for tag in BLOCK_TAG.captures_iter(&source[end..]).filter(|tag| tag[2] == name) {
+        if &tag[1] == "#" {
+            depth += 1;
+        } else if depth > 0 {
+            depth -= 1;
+        } else {
+            let close_start = end + tag.get(0).unwrap().start();
+            let Some(close_len) = source[close_start..].find("}}") else {
+                return false;
+            };
+            let close_end = close_start + close_len + 2;
+            let open = same_shape(&source[start..end], "{{#if 0", "}}");
+            let close = same_shape(&source[close_start..close_end], "{{/if", "}}");
+            match (open, close) {
+                (Some(open), Some(close)) => {
+                    source.replace_range(close_start..close_end, &close);
+                    source.replace_range(start..end, &open);
+                }
+                _ => {
+                    let blank = same_shape(&source[start..close_end], "", "").unwrap();
+                    source.replace_range(start..close_end, &blank);
+                }
+            }
+            return true;
+        }
+    }
+    false
+}
+
+/// Text with the same number of characters on each line as `original`: `head` at the start, `tail`
+/// at the end and spaces in between. `None` if `head` and `tail` do not fit.
+fn same_shape(original: &str, head: &str, tail: &str) -> Option<String> {
+    let mut out: Vec<char> = original.chars().map(|c| if c == '\n' { c } else { ' ' }).collect();
+    let first_line = out.iter().position(|c| *c == '\n').unwrap_or(out.len());
+    let last_line = out.iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1);
+    let (head_len, tail_len) = (head.chars().count(), tail.chars().count());
+    if head_len > first_line || tail_len > out.len() - last_line || (first_line == out.len() && head_len + tail_len > out.len()) {
+        return None;
+    }
+    let len = out.len();
+    out.splice(..head_len, head.chars());
+    out.splice(len - tail_len.., tail.chars());
+    Some(out.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn strict() -> Handlebars<'static> {
+        let mut hbs = Handlebars::new();
+        hbs.set_strict_mode(true);
+        hbs
+    }
+
+    fn positions(template: &str, data: &Json) -> Vec<(Option<String>, usize, usize)> {
+        let search = find_missing(&strict(), template, data);
+        assert!(search.error.is_none(), "unexpected error: {:?}", search.error);
+        assert!(!search.truncated);
+        search.missing.into_iter().map(|m| (m.path, m.line, m.column)).collect()
+    }
+
+    #[test]
+    fn reports_every_missing_variable() {
+        let data = json!({ "name": "Sui" });
+        let found = positions("{{name}} {{a}}\n{{b.c}}", &data);
+        assert_eq!(
+            found,
+            vec![(Some("a".into()), 1, 10), (Some("b.c".into()), 2, 1)]
+        );
+    }
+
+    #[test]
+    fn keeps_columns_after_a_miss_on_the_same_line() {
+        let found = positions("Start {{nets.mainet.url}} and {{#each missing}}x{{/each}}", &json!({}));
+        assert_eq!(
+            found,
+            vec![(Some("nets.mainet.url".into()), 1, 7), (Some("missing".into()), 1, 31)]
+        );
+    }
+
+    #[test]
+    fn keeps_columns_after_a_skipped_block() {
+        let found = positions("{{#each xs}}{{y}}{{/each}} {{z}}", &json!({}));
+        assert_eq!(found, vec![(Some("xs".into()), 1, 1), (Some("z".into()), 1, 28)]);
+    }
+
+    #[test]
+    fn neutralize_keeps_the_length_of_every_line() {
+        let mut source = "a {{#each xs}}b{{/each}} c".to_string();
+        assert!(neutralize(&mut source, 2, 14));
+        assert_eq!(source, "a {{#if 0   }}b{{/if  }} c");
+
+        // Tags too short for `{{#if 0}}` blank out the whole block.
+        let mut source = "{{#a x}}b{{/a}} {{y}}".to_string();
+        assert!(neutralize(&mut source, 0, 8));
+        assert_eq!(source, "                {{y}}");
+
+        let mut source = "{{x}}\n{{y}}".to_string();
+        assert!(neutralize(&mut source, 0, 5));
+        assert_eq!(source, "     \n{{y}}");
+    }
+
+    #[test]
+    fn keeps_lines_of_multiline_tags() {
+        let found = positions("{{#each\n  xs}}a{{/each}}\n{{y}}", &json!({}));
+        assert_eq!(found, vec![(Some("xs".into()), 1, 1), (Some("y".into()), 3, 1)]);
+    }
+
+    #[test]
+    fn returns_other_errors_with_the_variables_found_before() {
+        let search = find_missing(&strict(), "{{a}} {{nohelper 1}}", &json!({}));
+        assert_eq!(search.missing.len(), 1);
+        assert!(search.error.is_some());
+    }
+
+    #[test]
+    fn says_when_it_stops_early() {
+        let template: String = (0..MAX_PASSES + 2).map(|i| format!("{{{{v{}}}}}\n", i)).collect();
+        let search = find_missing(&strict(), &template, &json!({}));
+        assert_eq!(search.missing.len(), MAX_PASSES);
+        assert!(search.truncated);
+
+        let err = check(&strict(), &template, &json!({}), &[]).unwrap_err().to_string();
+        assert_eq!(err.lines().count(), MAX_PASSES + 1);
+        assert!(err.ends_with(&Search::truncated_message()), "{}", err);
+    }
+
+    #[test]
+    fn neutralize_matches_nested_blocks_of_the_same_name() {
+        let mut source = "{{#each a}}{{#each b}}x{{/each}}{{/each}} {{#eachx}}".to_string();
+        assert!(neutralize(&mut source, 0, 11));
+        assert_eq!(source, "{{#if 0  }}{{#each b}}x{{/each}}{{/if  }} {{#eachx}}");
+    }
+
+    #[test]
+    fn check_skips_allowed_paths() {
+        let allowed = [Regex::new("^page\\.[^.]+$").unwrap()];
+        assert!(check(&strict(), "{{page.title}} {{this.page.x}}", &json!({}), &allowed).is_ok());
+        let err = check(&strict(), "{{page.title}} {{other}}", &json!({}), &allowed).unwrap_err();
+        assert!(err.to_string().contains("`other`"), "{}", err);
+    }
+
+    #[test]
+    fn same_shape_keeps_line_lengths() {
+        assert_eq!(same_shape("{{#each xs}}", "{{#if 0", "}}").as_deref(), Some("{{#if 0   }}"));
+        assert_eq!(same_shape("{{a\nb}}", "", "").as_deref(), Some("   \n   "));
+        assert_eq!(same_shape("{{/x}}", "{{/if", "}}"), None);
+    }
+}